@@ -1,10 +1,18 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
 use git2::{BranchType, DiffOptions, Repository, Signature, StashFlags, StatusOptions};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use crate::models::{
-    BranchInfo, CommitInfo, ConflictInfo, DiffInfo, FileStatus, RepositoryInfo, StageResult,
-    StashInfo,
+    ApplyPatchOptions, BisectStatus, BisectStep, BranchInfo, CloneOptions, CoAuthor, CommitIdentity,
+    CommitInfo, CommitLintConfig, CommitLintResult, CommitLintViolation, CommitSearchMatch,
+    CommitSearchOptions, CommitSearchSummary, CommitTrailer, ConflictInfo, DiffFileList,
+    DiffFileSummary, DiffHunkInfo, DiffInfo, DiffLineInfo, DiffSource, DiffStats, DiffViewOptions,
+    DiscardFileResult, DiscardHunksOptions, DiscardResult, FileDiff, FileStatus, IdentityProfile,
+    ImageDiff, ImageSide, InitTemplates, LfsDiffInfo, LfsFileInfo, LfsObject, LfsProgress,
+    LineHighlight, PatchApplyResult, PatchFailure, RepositoryInfo, RevisionComparison, Settings,
+    StageResult, StashInfo, SubmoduleInfo, TextRange, WorktreeInfo,
 };
 
 pub fn open_repository(path: &str) -> Result<Repository, String> {
@@ -68,16 +76,78 @@ fn is_safe_git_arg(arg: &str) -> bool {
     !arg.contains('\\')
 }
 
+/// Partial clone filters git understands: `blob:none`, `blob:limit=<n>[kmg]` and `tree:<depth>`.
+fn is_valid_clone_filter(filter: &str) -> bool {
+    if filter == "blob:none" {
+        return true;
+    }
+    if let Some(limit) = filter.strip_prefix("blob:limit=") {
+        let digits = limit.trim_end_matches(['k', 'm', 'g']);
+        return !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit());
+    }
+    if let Some(depth) = filter.strip_prefix("tree:") {
+        return !depth.is_empty() && depth.chars().all(|c| c.is_ascii_digit());
+    }
+    false
+}
+
 pub fn clone_repository(
-    url: &str,
-    path: &str,
+    options: &CloneOptions,
     ssh_key_path: Option<&str>,
     _ssh_passphrase: Option<&str>,
 ) -> Result<Repository, String> {
+    let url = options.url.as_str();
+    let path = options.path.as_str();
     if url.contains(' ') || url.contains(';') || url.starts_with('-') {
         return Err("Invalid clone URL".to_string());
     }
-    
+
+    let target = Path::new(path);
+    if target.exists() {
+        let is_empty = target
+            .read_dir()
+            .map(|mut entries| entries.next().is_none())
+            .unwrap_or(false);
+        if !is_empty {
+            return Err("Target directory already exists and is not empty".to_string());
+        }
+    }
+
+    let depth = options.depth.map(|d| d.to_string());
+    let mut args = vec!["clone"];
+    if let Some(branch) = options.branch.as_deref().filter(|b| !b.is_empty()) {
+        if !is_safe_git_arg(branch) {
+            return Err("Invalid branch name".to_string());
+        }
+        args.extend(["--branch", branch]);
+    }
+    if let Some(depth) = depth.as_deref() {
+        if depth == "0" {
+            return Err("Clone depth must be at least 1".to_string());
+        }
+        args.extend(["--depth", depth]);
+    }
+    if options.single_branch {
+        args.push("--single-branch");
+    }
+    if let Some(filter) = options.filter.as_deref().filter(|f| !f.is_empty()) {
+        if !is_valid_clone_filter(filter) {
+            return Err("Invalid clone filter".to_string());
+        }
+        args.extend(["--filter", filter]);
+    }
+    if options.mirror {
+        args.push("--mirror");
+    } else if options.bare {
+        args.push("--bare");
+    }
+    if options.recurse_submodules && !options.mirror && !options.bare {
+        args.push("--recurse-submodules");
+        if depth.is_some() {
+            args.push("--shallow-submodules");
+        }
+    }
+
     let mut envs = Vec::new();
     if let Some(key) = ssh_key_path {
         if !key.trim().is_empty() {
@@ -95,10 +165,176 @@ pub fn clone_repository(
             ));
         }
     }
-    run_git_command(vec!["clone", "--", url, path], None, envs)?;
+    args.extend(["--", url, path]);
+    run_git_command(args, None, envs)?;
     open_repository(path)
 }
 
+const GITIGNORE_TEMPLATES: &[(&str, &str)] = &[
+    ("Go", include_str!("../templates/gitignore/Go.gitignore")),
+    ("Java", include_str!("../templates/gitignore/Java.gitignore")),
+    ("Node", include_str!("../templates/gitignore/Node.gitignore")),
+    ("Python", include_str!("../templates/gitignore/Python.gitignore")),
+    ("Rust", include_str!("../templates/gitignore/Rust.gitignore")),
+];
+
+const LICENSE_TEMPLATES: &[(&str, &str)] = &[
+    ("BSD-3-Clause", include_str!("../templates/license/BSD-3-Clause.txt")),
+    ("ISC", include_str!("../templates/license/ISC.txt")),
+    ("MIT", include_str!("../templates/license/MIT.txt")),
+    ("Unlicense", include_str!("../templates/license/Unlicense.txt")),
+];
+
+pub fn list_init_templates() -> InitTemplates {
+    InitTemplates {
+        gitignore: GITIGNORE_TEMPLATES.iter().map(|(name, _)| name.to_string()).collect(),
+        license: LICENSE_TEMPLATES.iter().map(|(name, _)| name.to_string()).collect(),
+    }
+}
+
+/// Checks template names up front so a typo is reported before the repository is created.
+pub fn validate_init_templates(
+    gitignore_template: Option<&str>,
+    license_template: Option<&str>,
+) -> Result<(), String> {
+    if let Some(name) = gitignore_template {
+        find_template(GITIGNORE_TEMPLATES, name)?;
+    }
+    if let Some(name) = license_template {
+        find_template(LICENSE_TEMPLATES, name)?;
+    }
+    Ok(())
+}
+
+fn find_template<'a>(templates: &'a [(&str, &str)], name: &str) -> Result<&'a str, String> {
+    templates
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, content)| *content)
+        .ok_or_else(|| format!("Unknown template '{}'", name))
+}
+
+fn current_year() -> i64 {
+    let days = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64 / 86_400)
+        .unwrap_or(0);
+    // Civil-from-days conversion (proleptic Gregorian calendar)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let month = (5 * doy + 2) / 153;
+    yoe + era * 400 + if month >= 10 { 1 } else { 0 }
+}
+
+/// Creates a new repository whose unborn HEAD points at `initial_branch`, optionally adding `origin`.
+pub fn init_repository(path: &str, initial_branch: &str, remote_url: Option<&str>) -> Result<Repository, String> {
+    if !is_safe_git_arg(initial_branch)
+        || !git2::Reference::is_valid_name(&format!("refs/heads/{}", initial_branch))
+    {
+        return Err("Invalid branch name".to_string());
+    }
+    let remote_url = remote_url.map(str::trim).filter(|u| !u.is_empty());
+    if let Some(url) = remote_url {
+        if url.contains(' ') || url.contains(';') || url.starts_with('-') {
+            return Err("Invalid remote URL".to_string());
+        }
+    }
+
+    let root = Path::new(path);
+    if root.join(".git").exists() || Repository::open(root).is_ok() {
+        return Err("A repository already exists at this path".to_string());
+    }
+
+    let mut opts = git2::RepositoryInitOptions::new();
+    opts.mkdir(true).mkpath(true).initial_head(initial_branch);
+    let repo = Repository::init_opts(root, &opts)
+        .map_err(|e| format!("Failed to initialize repository: {}", e))?;
+
+    if let Some(url) = remote_url {
+        repo.remote("origin", url)
+            .map_err(|e| format!("Failed to add remote: {}", e))?;
+    }
+
+    Ok(repo)
+}
+
+/// Writes README/.gitignore/LICENSE starter files into the working tree.
+/// Existing files are never overwritten. Returns the paths written so they can be committed.
+pub fn write_starter_files(
+    repo: &Repository,
+    readme: bool,
+    gitignore_template: Option<&str>,
+    license_template: Option<&str>,
+    copyright_holder: &str,
+) -> Result<Vec<String>, String> {
+    let root = repo.workdir().ok_or("No working directory found")?;
+
+    // Resolve templates before touching the disk so a typo doesn't leave half the files behind
+    let gitignore = gitignore_template
+        .map(|name| find_template(GITIGNORE_TEMPLATES, name))
+        .transpose()?;
+    let license = license_template
+        .map(|name| find_template(LICENSE_TEMPLATES, name))
+        .transpose()?
+        .map(|text| {
+            text.replace("{year}", &current_year().to_string())
+                .replace("{name}", copyright_holder)
+        });
+
+    let mut files: Vec<(&str, String)> = Vec::new();
+    if readme {
+        let title = root
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "Project".to_string());
+        files.push(("README.md", format!("# {}\n", title)));
+    }
+    if let Some(content) = gitignore {
+        files.push((".gitignore", content.to_string()));
+    }
+    if let Some(content) = license {
+        files.push(("LICENSE", content));
+    }
+
+    let mut written = Vec::new();
+    for (name, content) in files {
+        let file_path = root.join(name);
+        if file_path.exists() {
+            continue;
+        }
+        std::fs::write(&file_path, content).map_err(|e| format!("Failed to write {}: {}", name, e))?;
+        written.push(name.to_string());
+    }
+
+    Ok(written)
+}
+
+/// 移除末尾斜線，確保路徑格式一致
+pub fn normalize_path(path: &Path) -> String {
+    let mut path = path.to_string_lossy().to_string();
+    while path.ends_with('/') || path.ends_with('\\') {
+        path.pop();
+    }
+    path
+}
+
+/// Name of an in-progress operation, as reported in repo-state-changed events.
+pub fn repository_state_name(state: git2::RepositoryState) -> &'static str {
+    use git2::RepositoryState::*;
+    match state {
+        Clean => "clean",
+        Merge => "merge",
+        Revert | RevertSequence => "revert",
+        CherryPick | CherryPickSequence => "cherry-pick",
+        Bisect => "bisect",
+        Rebase | RebaseInteractive | RebaseMerge | ApplyMailboxOrRebase => "rebase",
+        ApplyMailbox => "apply-mailbox",
+    }
+}
+
 pub fn get_repository_info(repo: &Repository) -> Result<RepositoryInfo, String> {
     let mut ahead = 0;
     let mut behind = 0;
@@ -139,21 +375,18 @@ pub fn get_repository_info(repo: &Repository) -> Result<RepositoryInfo, String>
         }
     }
 
-    let statuses = repo
-        .statuses(None)
-        .map_err(|e| format!("Failed to get statuses: {}", e))?;
+    let is_dirty = is_dirty(repo)?;
 
-    let is_dirty = !statuses.is_empty();
+    let path = normalize_path(repo.workdir().unwrap_or_else(|| repo.path()));
 
-    let mut path = repo
-        .workdir()
-        .map(|p| p.to_string_lossy().to_string())
-        .unwrap_or_else(|| repo.path().to_string_lossy().to_string());
-    
-    // 移除末尾斜線，確保路徑格式一致
-    while path.ends_with('/') || path.ends_with('\\') {
-        path.pop();
-    }
+    let is_worktree = repo.is_worktree();
+    let main_repository_path = if is_worktree {
+        Repository::open(common_git_dir(repo))
+            .ok()
+            .and_then(|main| main.workdir().map(normalize_path))
+    } else {
+        None
+    };
 
     Ok(RepositoryInfo {
         path,
@@ -161,57 +394,465 @@ pub fn get_repository_info(repo: &Repository) -> Result<RepositoryInfo, String>
         is_dirty,
         ahead,
         behind,
+        is_worktree,
+        main_repository_path,
     })
 }
 
-pub fn get_status(repo: &Repository) -> Result<Vec<FileStatus>, String> {
+fn submodule_state(repo: &Repository, path: &str) -> Option<String> {
+    let name = repo
+        .submodules()
+        .ok()?
+        .iter()
+        .find(|s| s.path() == Path::new(path))?
+        .name()?
+        .to_string();
+    let status = repo
+        .submodule_status(&name, git2::SubmoduleIgnore::None)
+        .ok()?;
+
+    let state = if status.contains(git2::SubmoduleStatus::WD_UNINITIALIZED) {
+        "uninitialized"
+    } else if status.contains(git2::SubmoduleStatus::WD_MODIFIED) {
+        "new-commits"
+    } else if status.intersects(
+        git2::SubmoduleStatus::WD_INDEX_MODIFIED | git2::SubmoduleStatus::WD_WD_MODIFIED,
+    ) {
+        "modified-content"
+    } else if status.contains(git2::SubmoduleStatus::WD_UNTRACKED) {
+        "untracked-content"
+    } else {
+        "clean"
+    };
+    Some(state.to_string())
+}
+
+/// Builds status options, honouring `status.showUntrackedFiles` when it is set.
+/// Without it untracked directories are recursed so every file can be staged individually.
+fn status_options(repo: &Repository, include_ignored: bool) -> StatusOptions {
+    let show_untracked = repo
+        .config()
+        .and_then(|c| c.get_string("status.showUntrackedFiles"))
+        .unwrap_or_else(|_| "all".to_string());
+
     let mut opts = StatusOptions::new();
-    opts.include_untracked(true);
-    opts.recurse_untracked_dirs(true);
+    opts.include_untracked(show_untracked != "no");
+    opts.recurse_untracked_dirs(show_untracked != "normal");
+    opts.include_ignored(include_ignored);
+    opts.renames_head_to_index(true);
+    opts.renames_index_to_workdir(true);
+    opts
+}
+
+pub fn get_status(repo: &Repository, include_ignored: bool) -> Result<Vec<FileStatus>, String> {
+    let mut opts = status_options(repo, include_ignored);
+    collect_statuses(repo, &mut opts)
+}
 
+fn collect_statuses(repo: &Repository, opts: &mut StatusOptions) -> Result<Vec<FileStatus>, String> {
     let statuses = repo
-        .statuses(Some(&mut opts))
+        .statuses(Some(opts))
         .map_err(|e| format!("Failed to get status: {}", e))?;
 
+    let submodule_paths: Vec<String> = repo
+        .submodules()
+        .map(|subs| subs.iter().map(|s| s.path().to_string_lossy().to_string()).collect())
+        .unwrap_or_default();
+
     let mut file_statuses = Vec::new();
 
     for entry in statuses.iter() {
         let status = entry.status();
-        let path = entry.path().unwrap_or("unknown").to_string();
-
-        let status_str =
-            if status.is_index_new() || status.is_index_modified() || status.is_index_deleted() {
-                if status.is_index_new() {
-                    "added"
-                } else if status.is_index_modified() {
-                    "modified"
-                } else {
-                    "deleted"
-                }
-            } else if status.is_wt_new() {
-                "untracked"
-            } else if status.is_wt_modified() {
-                "modified"
-            } else if status.is_wt_deleted() {
-                "deleted"
-            } else {
-                "unknown"
-            };
 
-        let staged =
-            status.is_index_new() || status.is_index_modified() || status.is_index_deleted();
+        // With rename detection the entry path is the old name; report the new one
+        let head_to_index = entry.head_to_index();
+        let index_to_workdir = entry.index_to_workdir();
+        let new_path = index_to_workdir
+            .as_ref()
+            .and_then(|d| d.new_file().path())
+            .or_else(|| head_to_index.as_ref().and_then(|d| d.new_file().path()))
+            .and_then(|p| p.to_str());
+        let path = new_path.or(entry.path()).unwrap_or("unknown").to_string();
+
+        let old_path = [head_to_index.as_ref(), index_to_workdir.as_ref()]
+            .into_iter()
+            .flatten()
+            .find(|d| d.status() == git2::Delta::Renamed)
+            .and_then(|d| d.old_file().path())
+            .and_then(|p| p.to_str())
+            .map(|p| p.to_string());
+
+        let index_status = if status.is_index_new() {
+            Some("added")
+        } else if status.is_index_renamed() {
+            Some("renamed")
+        } else if status.is_index_typechange() {
+            Some("typechange")
+        } else if status.is_index_modified() {
+            Some("modified")
+        } else if status.is_index_deleted() {
+            Some("deleted")
+        } else {
+            None
+        };
+
+        let worktree_status = if status.is_wt_new() {
+            Some("untracked")
+        } else if status.is_wt_renamed() {
+            Some("renamed")
+        } else if status.is_wt_typechange() {
+            Some("typechange")
+        } else if status.is_wt_modified() {
+            Some("modified")
+        } else if status.is_wt_deleted() {
+            Some("deleted")
+        } else {
+            None
+        };
+
+        let conflicted = status.is_conflicted();
+        let ignored = status.is_ignored();
+
+        // Summary kept for callers that show a file in a single list
+        let status_str = if conflicted {
+            "conflicted"
+        } else if ignored {
+            "ignored"
+        } else {
+            index_status.or(worktree_status).unwrap_or("unknown")
+        };
+        let staged = index_status.is_some();
+
+        let is_submodule = submodule_paths.contains(&path);
+        let submodule_state = if is_submodule {
+            submodule_state(repo, &path)
+        } else {
+            None
+        };
 
         file_statuses.push(FileStatus {
             path,
             status: status_str.to_string(),
             staged,
+            is_submodule,
+            index_status: index_status.map(|s| s.to_string()),
+            worktree_status: worktree_status.map(|s| s.to_string()),
+            old_path,
+            conflicted,
+            ignored,
+            submodule_state,
         });
     }
 
     Ok(file_statuses)
 }
 
+/// Identifies a state of HEAD and the index; cached statuses are stale once it changes.
+#[derive(PartialEq, Clone, Copy)]
+struct StatusKey {
+    head: Option<git2::Oid>,
+    index_modified: Option<std::time::SystemTime>,
+    index_len: u64,
+}
+
+fn status_key(repo: &Repository) -> StatusKey {
+    let index_meta = std::fs::metadata(repo.path().join("index")).ok();
+    StatusKey {
+        head: repo.head().ok().and_then(|h| h.target()),
+        index_modified: index_meta.as_ref().and_then(|m| m.modified().ok()),
+        index_len: index_meta.map(|m| m.len()).unwrap_or(0),
+    }
+}
+
+/// Keeps the last `get_status` result so refreshes only rescan paths reported by the file watcher.
+/// Without a working-tree watcher nothing reports edits, so every request is a full scan.
+#[derive(Default)]
+pub struct StatusCache {
+    entries: Option<Vec<FileStatus>>,
+    include_ignored: bool,
+    key: Option<StatusKey>,
+    pending: Vec<String>,
+    incremental: bool,
+}
+
+impl StatusCache {
+    /// `incremental` should only be set when a watcher reports working-tree edits via `mark_paths`.
+    pub fn new(incremental: bool) -> Self {
+        StatusCache {
+            incremental,
+            ..Default::default()
+        }
+    }
+
+    pub fn invalidate(&mut self) {
+        self.entries = None;
+        self.pending.clear();
+    }
+
+    /// Records working-tree paths (relative to the workdir) that changed since the last refresh.
+    pub fn mark_paths<I: IntoIterator<Item = String>>(&mut self, paths: I) {
+        if self.entries.is_some() {
+            self.pending.extend(paths);
+        }
+    }
+
+    pub fn get(&mut self, repo: &Repository, include_ignored: bool) -> Result<Vec<FileStatus>, String> {
+        let key = status_key(repo);
+        // Ignored files are never reported by the watcher, so listing them always rescans
+        let reusable = self.incremental
+            && !include_ignored
+            && !self.include_ignored
+            && self.entries.is_some()
+            && self.key == Some(key);
+
+        if !reusable || !self.refresh_pending(repo)? {
+            self.entries = Some(get_status(repo, include_ignored)?);
+        }
+        self.include_ignored = include_ignored;
+        self.key = Some(key);
+        self.pending.clear();
+        Ok(self.entries.clone().unwrap_or_default())
+    }
+
+    /// Rescans only the pending paths. Returns false when a full scan is needed instead.
+    fn refresh_pending(&mut self, repo: &Repository) -> Result<bool, String> {
+        if self.pending.is_empty() {
+            return Ok(true);
+        }
+        let workdir = match repo.workdir() {
+            Some(w) => w,
+            None => return Ok(false),
+        };
+        let mut paths = std::mem::take(&mut self.pending);
+        paths.sort();
+        paths.dedup();
+        // Directory events can hide any number of files; exact-path matching can't cover them
+        if paths.iter().any(|p| workdir.join(p).is_dir()) {
+            return Ok(false);
+        }
+
+        let mut opts = status_options(repo, self.include_ignored);
+        opts.disable_pathspec_match(true);
+        for path in &paths {
+            opts.pathspec(path);
+        }
+        let fresh = collect_statuses(repo, &mut opts)?;
+
+        let entries = match self.entries.as_mut() {
+            Some(e) => e,
+            None => return Ok(false),
+        };
+        let touched = |f: &FileStatus| {
+            paths
+                .iter()
+                .any(|p| f.path == *p || f.path.strip_prefix(p.as_str()).is_some_and(|r| r.starts_with('/')))
+        };
+        // Renames pair two paths; only a full scan can pair them correctly
+        if fresh.iter().any(|f| f.old_path.is_some())
+            || entries.iter().any(|f| touched(f) && f.old_path.is_some())
+        {
+            return Ok(false);
+        }
+
+        entries.retain(|f| !touched(f));
+        entries.extend(fresh);
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(true)
+    }
+}
+
+/// Whether git was configured with an untracked cache or fsmonitor, which only the git CLI uses.
+fn has_status_acceleration(repo: &Repository) -> bool {
+    repo.config()
+        .map(|c| {
+            c.get_bool("core.untrackedCache").unwrap_or(false)
+                || c.get_string("core.fsmonitor").map(|v| v != "false").unwrap_or(false)
+        })
+        .unwrap_or(false)
+}
+
+/// Cheap dirty check that stops at the first change instead of computing the full status.
+/// Tracked files are checked by stat data; finding untracked files still walks the working
+/// tree, which git's untracked cache or fsmonitor speeds up when they are enabled.
+pub fn is_dirty(repo: &Repository) -> Result<bool, String> {
+    let workdir = match repo.workdir() {
+        Some(w) => w,
+        None => return Ok(false), // bare repositories have no working tree
+    };
+    let index = repo
+        .index()
+        .map_err(|e| format!("Failed to get index: {}", e))?;
+
+    // 1. Staged changes: HEAD vs index touches no working-tree files
+    if index.has_conflicts() {
+        return Ok(true);
+    }
+    let head_tree = repo.head().ok().and_then(|h| h.peel_to_tree().ok());
+    match head_tree {
+        Some(tree) => {
+            let diff = repo
+                .diff_tree_to_index(Some(&tree), Some(&index), None)
+                .map_err(|e| format!("Failed to diff index: {}", e))?;
+            if diff.deltas().len() > 0 {
+                return Ok(true);
+            }
+        }
+        None => {
+            if !index.is_empty() {
+                return Ok(true);
+            }
+        }
+    }
+
+    // 2. Unstaged changes: compare index stat data, hashing only when timestamps disagree.
+    // Entries not strictly older than the index file are "racy" like in git: an edit in the same
+    // instant keeps the stat data, so their content is always compared.
+    let index_mtime = std::fs::metadata(repo.path().join("index"))
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok());
+    for entry in index.iter() {
+        // Gitlinks (submodules) have their own dirty state
+        if entry.mode == 0o160000 {
+            continue;
+        }
+        let path = String::from_utf8_lossy(&entry.path).to_string();
+        let meta = match std::fs::symlink_metadata(workdir.join(&path)) {
+            Ok(m) => m,
+            Err(_) => return Ok(true), // deleted
+        };
+        // The index keeps only the low 32 bits of the size, so compare truncated like git does
+        if meta.len() as u32 != entry.file_size && !meta.file_type().is_symlink() {
+            return Ok(true);
+        }
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok());
+        // A zero nanosecond field means the index only recorded whole seconds
+        let nanos = entry.mtime.nanoseconds();
+        let unchanged = mtime
+            .map(|t| {
+                t.as_secs() as i32 == entry.mtime.seconds() && (nanos == 0 || t.subsec_nanos() == nanos)
+            })
+            .unwrap_or(false);
+        let entry_mtime = std::time::Duration::new(entry.mtime.seconds() as u64, nanos);
+        let racy = match index_mtime {
+            Some(t) if nanos == 0 => entry_mtime.as_secs() >= t.as_secs(),
+            Some(t) => entry_mtime >= t,
+            None => true,
+        };
+        if racy || !unchanged {
+            let status = repo
+                .status_file(Path::new(&path))
+                .unwrap_or(git2::Status::WT_MODIFIED);
+            if status.intersects(
+                git2::Status::WT_MODIFIED | git2::Status::WT_DELETED | git2::Status::WT_TYPECHANGE,
+            ) {
+                return Ok(true);
+            }
+        }
+    }
+
+    // 3. Untracked files
+    if has_status_acceleration(repo) {
+        if let Some(found) = first_untracked_via_cli(workdir) {
+            return Ok(found);
+        }
+    }
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(true);
+    opts.recurse_untracked_dirs(false);
+    opts.exclude_submodules(true);
+    let statuses = repo
+        .statuses(Some(&mut opts))
+        .map_err(|e| format!("Failed to get statuses: {}", e))?;
+    Ok(statuses.iter().any(|e| e.status().is_wt_new()))
+}
+
+/// Asks `git ls-files` (which uses the untracked cache and fsmonitor) for the first untracked path.
+/// Returns None when git can't be run, so callers fall back to libgit2.
+fn first_untracked_via_cli(workdir: &Path) -> Option<bool> {
+    use std::io::Read;
+
+    let mut child = Command::new("git")
+        .args(["ls-files", "--others", "--exclude-standard", "--directory", "--no-empty-directory", "-z"])
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .current_dir(workdir)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .ok()?;
+
+    let mut first = [0u8; 1];
+    let found = child.stdout.take()?.read(&mut first).ok()? > 0;
+    if found {
+        let _ = child.kill();
+    }
+    let status = child.wait().ok()?;
+    if found || status.success() {
+        Some(found)
+    } else {
+        None
+    }
+}
+
+/// Old paths of the renames whose new path is in `paths`, so that acting on a rename entry from
+/// `get_status` also covers the name it was renamed from. Renames between HEAD and the index
+/// and/or between the index and the working tree are considered as selected.
+fn rename_sources(
+    repo: &Repository,
+    paths: &[String],
+    head_to_index: bool,
+    index_to_workdir: bool,
+) -> Vec<String> {
+    // A rename's new path is never in HEAD, so most selections need no status scan
+    let head_tree = repo.head().ok().and_then(|h| h.peel_to_tree().ok());
+    let candidates: Vec<&String> = paths
+        .iter()
+        .filter(|p| {
+            let in_head = head_tree.as_ref().map(|t| t.get_path(Path::new(p.as_str())).is_ok());
+            !in_head.unwrap_or(false)
+        })
+        .collect();
+    if candidates.is_empty() {
+        return Vec::new();
+    }
+
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(index_to_workdir)
+        .recurse_untracked_dirs(index_to_workdir)
+        .renames_head_to_index(head_to_index)
+        .renames_index_to_workdir(index_to_workdir);
+    let statuses = match repo.statuses(Some(&mut opts)) {
+        Ok(s) => s,
+        Err(_) => return Vec::new(),
+    };
+    let mut sources = Vec::new();
+    for entry in statuses.iter() {
+        let deltas = [
+            entry.head_to_index().filter(|_| head_to_index),
+            entry.index_to_workdir().filter(|_| index_to_workdir),
+        ];
+        for delta in deltas.into_iter().flatten() {
+            if delta.status() != git2::Delta::Renamed {
+                continue;
+            }
+            let new_path = delta.new_file().path().map(normalize_path);
+            let old_path = delta.old_file().path().map(normalize_path);
+            if let (Some(new_path), Some(old_path)) = (new_path, old_path) {
+                if candidates.iter().any(|p| **p == new_path) && !sources.contains(&old_path) {
+                    sources.push(old_path);
+                }
+            }
+        }
+    }
+    sources
+}
+
+/// Stages the given paths. For a working-tree rename the old path's deletion is staged too.
 pub fn stage_files(repo: &Repository, paths: Vec<String>) -> Result<StageResult, String> {
+    let sources = rename_sources(repo, &paths, false, true);
     let mut index = repo
         .index()
         .map_err(|e| format!("Failed to get index: {}", e))?;
@@ -233,6 +874,12 @@ pub fn stage_files(repo: &Repository, paths: Vec<String>) -> Result<StageResult,
             warnings.push(format!("Skipped '{}': file not found (removed from index)", path));
         }
     }
+    for source in sources {
+        match index.remove_path(Path::new(&source)) {
+            Ok(_) => staged.push(source),
+            Err(e) => warnings.push(format!("Failed to stage '{}': {}", source, e)),
+        }
+    }
 
     index
         .write()
@@ -241,7 +888,9 @@ pub fn stage_files(repo: &Repository, paths: Vec<String>) -> Result<StageResult,
     Ok(StageResult { staged, warnings })
 }
 
-pub fn unstage_files(repo: &Repository, paths: Vec<String>) -> Result<(), String> {
+/// Unstages the given paths. For a staged rename the old path is put back in the index too.
+pub fn unstage_files(repo: &Repository, mut paths: Vec<String>) -> Result<(), String> {
+    paths.extend(rename_sources(repo, &paths, true, false));
     let head = repo.head().ok();
     let commit = head.and_then(|h| h.peel_to_commit().ok());
 
@@ -291,11 +940,182 @@ pub fn create_safety_ref(repo: &Repository, action_name: &str) -> Result<(), Str
     Ok(())
 }
 
-pub fn amend_last_commit(repo: &Repository, message: &str) -> Result<String, String> {
+const PLACEHOLDER_NAME: &str = "User";
+const PLACEHOLDER_EMAIL: &str = "user@example.com";
+
+fn is_placeholder_identity(name: &str, email: &str) -> bool {
+    name.trim().is_empty() || email.trim().is_empty() || email.trim() == PLACEHOLDER_EMAIL
+}
+
+/// Extracts the host from an HTTPS, SSH or scp-style remote URL.
+fn remote_host(url: &str) -> Option<String> {
+    let rest = match url.find("://") {
+        Some(idx) => &url[idx + 3..],
+        None => url,
+    };
+    let rest = rest.rsplit_once('@').map(|(_, r)| r).unwrap_or(rest);
+    let host = rest.split(['/', ':']).next()?;
+    if host.is_empty() {
+        None
+    } else {
+        Some(host.to_lowercase())
+    }
+}
+
+fn expand_home(path: &str) -> String {
+    if path.starts_with("~/") {
+        path.replacen("~", &std::env::var("HOME").unwrap_or_default(), 1)
+    } else {
+        path.to_string()
+    }
+}
+
+fn matching_profile<'a>(repo: &Repository, profiles: &'a [IdentityProfile]) -> Option<&'a IdentityProfile> {
+    let repo_path = repo.workdir().unwrap_or_else(|| repo.path());
+    let hosts: Vec<String> = repo
+        .remotes()
+        .map(|names| {
+            names
+                .iter()
+                .flatten()
+                .filter_map(|name| repo.find_remote(name).ok())
+                .filter_map(|remote| remote.url().and_then(remote_host))
+                .collect()
+        })
+        .unwrap_or_default();
+    profile_for(repo_path, &hosts, profiles)
+}
+
+fn profile_for<'a>(
+    repo_path: &Path,
+    hosts: &[String],
+    profiles: &'a [IdentityProfile],
+) -> Option<&'a IdentityProfile> {
+    profiles.iter().find(|profile| {
+        let path_match = profile
+            .path_prefix
+            .as_deref()
+            .filter(|p| !p.trim().is_empty())
+            .map(|p| repo_path.starts_with(expand_home(p)));
+        let host_match = profile
+            .remote_host
+            .as_deref()
+            .filter(|h| !h.trim().is_empty())
+            .map(|h| hosts.iter().any(|host| host.eq_ignore_ascii_case(h.trim())));
+        path_match.unwrap_or(false) || host_match.unwrap_or(false)
+    })
+}
+
+/// Resolves who commits in this repository.
+/// Order: repository-local git config, a matching identity profile, any other git config,
+/// the app settings, and finally the placeholder identity if the settings allow it.
+pub fn resolve_identity(repo: &Repository, settings: &Settings) -> Result<CommitIdentity, String> {
+    let identity = |name: &str, email: &str, source: &str| CommitIdentity {
+        name: name.to_string(),
+        email: email.to_string(),
+        source: source.to_string(),
+    };
+
+    if let Ok(local) = repo
+        .config()
+        .and_then(|c| c.open_level(git2::ConfigLevel::Local))
+    {
+        if let (Ok(name), Ok(email)) = (local.get_string("user.name"), local.get_string("user.email")) {
+            if !is_placeholder_identity(&name, &email) {
+                return Ok(identity(&name, &email, "repository"));
+            }
+        }
+    }
+
+    let profile = matching_profile(repo, &settings.identity_profiles);
+    let signature = repo.signature().ok();
+    fallback_identity(profile, signature.as_ref(), settings)
+}
+
+/// Identity for a repository that `init_repository` is about to create, resolved the same way
+/// as `resolve_identity` (a new repository has no local config yet), so a missing identity is
+/// reported before anything is written to disk.
+pub fn resolve_new_repo_identity(
+    path: &str,
+    remote_url: Option<&str>,
+    settings: &Settings,
+) -> Result<CommitIdentity, String> {
+    let hosts: Vec<String> = remote_url.and_then(remote_host).into_iter().collect();
+    let profile = profile_for(Path::new(path), &hosts, &settings.identity_profiles);
+    let signature = git2::Config::open_default()
+        .and_then(|config| {
+            let name = config.get_string("user.name")?;
+            let email = config.get_string("user.email")?;
+            Signature::now(&name, &email)
+        })
+        .ok();
+    fallback_identity(profile, signature.as_ref(), settings)
+}
+
+/// The part of identity resolution after the repository-local config.
+fn fallback_identity(
+    profile: Option<&IdentityProfile>,
+    signature: Option<&Signature>,
+    settings: &Settings,
+) -> Result<CommitIdentity, String> {
+    let identity = |name: &str, email: &str, source: &str| CommitIdentity {
+        name: name.to_string(),
+        email: email.to_string(),
+        source: source.to_string(),
+    };
+
+    if let Some(profile) = profile {
+        if !is_placeholder_identity(&profile.user_name, &profile.user_email) {
+            return Ok(identity(
+                &profile.user_name,
+                &profile.user_email,
+                &format!("profile:{}", profile.name),
+            ));
+        }
+    }
+
+    if let Some(sig) = signature {
+        let (name, email) = (sig.name().unwrap_or(""), sig.email().unwrap_or(""));
+        if !is_placeholder_identity(name, email) {
+            return Ok(identity(name, email, "git-config"));
+        }
+    }
+
+    if !is_placeholder_identity(&settings.user_name, &settings.user_email) {
+        return Ok(identity(&settings.user_name, &settings.user_email, "settings"));
+    }
+
+    if settings.allow_placeholder_identity {
+        return Ok(identity(PLACEHOLDER_NAME, PLACEHOLDER_EMAIL, "placeholder"));
+    }
+
+    Err("No commit identity configured. Set user.name and user.email in git config or in Settings.".to_string())
+}
+
+pub fn identity_signature(identity: &CommitIdentity) -> Result<Signature<'static>, String> {
+    Signature::now(&identity.name, &identity.email)
+        .map_err(|e| format!("Failed to create signature: {}", e))
+}
+
+/// Amends HEAD with the current index. The original author is kept unless `reset_author` is set;
+/// the committer is always updated.
+pub fn amend_last_commit(
+    repo: &Repository,
+    message: &str,
+    signature: &Signature,
+    reset_author: bool,
+    skip_hooks: bool,
+) -> Result<String, String> {
+    let message = run_pre_commit_hooks(repo, message, &["commit", "HEAD"], skip_hooks)?;
+
     create_safety_ref(repo, "amend")?;
     let mut index = repo
         .index()
         .map_err(|e| format!("Failed to get index: {}", e))?;
+    // pre-commit may have staged files through the git CLI
+    index
+        .read(true)
+        .map_err(|e| format!("Failed to read index: {}", e))?;
 
     let tree_id = index
         .write_tree()
@@ -305,11 +1125,6 @@ pub fn amend_last_commit(repo: &Repository, message: &str) -> Result<String, Str
         .find_tree(tree_id)
         .map_err(|e| format!("Failed to find tree: {}", e))?;
 
-    let signature = repo
-        .signature()
-        .or_else(|_| Signature::now("User", "user@example.com"))
-        .map_err(|e| format!("Failed to create signature: {}", e))?;
-
     let head = repo
         .head()
         .map_err(|e| format!("Failed to get HEAD: {}", e))?;
@@ -320,18 +1135,20 @@ pub fn amend_last_commit(repo: &Repository, message: &str) -> Result<String, Str
     let commit_id = last_commit
         .amend(
             Some("HEAD"),
-            Some(&signature),
-            Some(&signature),
+            reset_author.then_some(signature),
+            Some(signature),
             None,
-            Some(message),
+            Some(&message),
             Some(&tree),
         )
         .map_err(|e| format!("Failed to amend commit: {}", e))?;
 
+    let _ = run_hook(repo, "post-commit", &[]);
+
     Ok(commit_id.to_string())
 }
 
-pub fn cherry_pick(repo: &Repository, sha: &str) -> Result<(), String> {
+pub fn cherry_pick(repo: &Repository, sha: &str, signature: &Signature) -> Result<(), String> {
     create_safety_ref(repo, "cherry-pick")?;
     let commit = repo
         .find_commit(git2::Oid::from_str(sha).map_err(|e| e.to_string())?)
@@ -348,15 +1165,13 @@ pub fn cherry_pick(repo: &Repository, sha: &str) -> Result<(), String> {
 
     let tree_id = index.write_tree().map_err(|e| e.to_string())?;
     let tree = repo.find_tree(tree_id).map_err(|e| e.to_string())?;
-    let signature = repo.signature().map_err(|e| e.to_string())?;
-    
     let head = repo.head().map_err(|e| format!("Failed to get HEAD: {}", e))?;
     let parent = head.peel_to_commit().map_err(|e| format!("Failed to peel HEAD: {}", e))?;
 
     repo.commit(
         Some("HEAD"),
-        &signature,
-        &signature,
+        signature,
+        signature,
         commit.message().unwrap_or("Cherry-picked commit"),
         &tree,
         &[&parent],
@@ -366,7 +1181,7 @@ pub fn cherry_pick(repo: &Repository, sha: &str) -> Result<(), String> {
     Ok(())
 }
 
-pub fn revert_commit(repo: &Repository, sha: &str) -> Result<(), String> {
+pub fn revert_commit(repo: &Repository, sha: &str, signature: &Signature) -> Result<(), String> {
     create_safety_ref(repo, "revert")?;
     let commit = repo
         .find_commit(git2::Oid::from_str(sha).map_err(|e| e.to_string())?)
@@ -383,15 +1198,13 @@ pub fn revert_commit(repo: &Repository, sha: &str) -> Result<(), String> {
 
     let tree_id = index.write_tree().map_err(|e| e.to_string())?;
     let tree = repo.find_tree(tree_id).map_err(|e| e.to_string())?;
-    let signature = repo.signature().map_err(|e| e.to_string())?;
-    
     let head = repo.head().map_err(|e| format!("Failed to get HEAD: {}", e))?;
     let parent = head.peel_to_commit().map_err(|e| format!("Failed to peel HEAD: {}", e))?;
 
     repo.commit(
         Some("HEAD"),
-        &signature,
-        &signature,
+        signature,
+        signature,
         &format!("Revert \"{}\"", commit.message().unwrap_or("")),
         &tree,
         &[&parent],
@@ -402,24 +1215,42 @@ pub fn revert_commit(repo: &Repository, sha: &str) -> Result<(), String> {
 }
 
 pub fn discard_changes(repo: &Repository, path: &str) -> Result<(), String> {
-    let mut checkout_opts = git2::build::CheckoutBuilder::new();
-    checkout_opts.force().path(path);
+    snapshot_worktree_files(repo, "discard", &[path.to_string()])?;
+    discard_path(repo, path)
+}
 
-    // Attempt checkout from HEAD
-    if repo.checkout_head(Some(&mut checkout_opts)).is_err() {
-        // If checkout head fails (e.g. untracked file), try to remove it
-        let full_path = repo.workdir().ok_or("No workdir")?.join(path);
-        if full_path.exists() {
-            if full_path.is_file() {
-                std::fs::remove_file(full_path)
-                    .map_err(|e| format!("Failed to delete file: {}", e))?;
-            } else if full_path.is_dir() {
-                std::fs::remove_dir_all(full_path)
-                    .map_err(|e| format!("Failed to delete dir: {}", e))?;
-            }
-        }
+fn discard_path(repo: &Repository, path: &str) -> Result<(), String> {
+    let in_head = repo
+        .head()
+        .ok()
+        .and_then(|h| h.peel_to_tree().ok())
+        .map(|t| t.get_path(Path::new(path.trim_end_matches('/'))).is_ok())
+        .unwrap_or(false);
+
+    if in_head {
+        let mut checkout_opts = git2::build::CheckoutBuilder::new();
+        checkout_opts.force().path(path);
+        return repo
+            .checkout_head(Some(&mut checkout_opts))
+            .map_err(|e| format!("Failed to discard changes: {}", e));
     }
 
+    // Untracked or newly added: drop it from the index and delete it
+    let mut index = repo
+        .index()
+        .map_err(|e| format!("Failed to get index: {}", e))?;
+    if index.get_path(Path::new(path), 0).is_some() {
+        index
+            .remove_path(Path::new(path))
+            .and_then(|_| index.write())
+            .map_err(|e| format!("Failed to update index: {}", e))?;
+    }
+    let full_path = repo.workdir().ok_or("No workdir")?.join(path);
+    if full_path.is_dir() {
+        std::fs::remove_dir_all(full_path).map_err(|e| format!("Failed to delete dir: {}", e))?;
+    } else if std::fs::symlink_metadata(&full_path).is_ok() {
+        std::fs::remove_file(full_path).map_err(|e| format!("Failed to delete file: {}", e))?;
+    }
     Ok(())
 }
 
@@ -431,6 +1262,339 @@ pub fn discard_all_changes(repo: &Repository) -> Result<(), String> {
         .map_err(|e| format!("Failed to discard all changes: {}", e))
 }
 
+/// Limits on what a safety snapshot saves, so discarding something like an untracked build
+/// directory can't write gigabytes into the object database.
+const SNAPSHOT_MAX_FILES: usize = 5_000;
+const SNAPSHOT_MAX_BYTES: u64 = 200 * 1024 * 1024;
+
+/// Collects the files under `path` (relative to the workdir), descending into directories but
+/// skipping ignored entries inside them. `total` accumulates file sizes for the limits above.
+fn collect_worktree_files(
+    repo: &Repository,
+    workdir: &Path,
+    path: &str,
+    files: &mut Vec<String>,
+    total: &mut u64,
+) -> Result<(), String> {
+    let full_path = workdir.join(path);
+    let meta = match std::fs::symlink_metadata(&full_path) {
+        Ok(m) => m,
+        Err(_) => return Ok(()), // already gone, nothing to save
+    };
+    if !meta.is_dir() {
+        files.push(path.trim_end_matches('/').to_string());
+        *total += meta.len();
+        if files.len() > SNAPSHOT_MAX_FILES || *total > SNAPSHOT_MAX_BYTES {
+            return Err(format!(
+                "Too many or too large files under '{}' to keep a safety snapshot (limit {} files, {} MB)",
+                path,
+                SNAPSHOT_MAX_FILES,
+                SNAPSHOT_MAX_BYTES / (1024 * 1024)
+            ));
+        }
+        return Ok(());
+    }
+    if let Ok(entries) = std::fs::read_dir(&full_path) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let child = format!("{}/{}", path.trim_end_matches('/'), name);
+            if name == ".git" || repo.is_path_ignored(&child).unwrap_or(false) {
+                continue;
+            }
+            collect_worktree_files(repo, workdir, &child, files, total)?;
+        }
+    }
+    Ok(())
+}
+
+/// Saves the working-tree content of `paths` (tracked or not) as a commit on top of HEAD under
+/// `refs/safety/<action>/`, so discarded edits can be restored with `restore_discarded`.
+/// Returns None when none of the paths exist, and an error (before writing anything) when they
+/// hold more than the snapshot limits allow.
+pub fn snapshot_worktree_files(
+    repo: &Repository,
+    action: &str,
+    paths: &[String],
+) -> Result<Option<String>, String> {
+    let workdir = repo.workdir().ok_or("No working directory found")?;
+    let mut files = Vec::new();
+    let mut total = 0;
+    for path in paths {
+        collect_worktree_files(repo, workdir, path, &mut files, &mut total)?;
+    }
+    if files.is_empty() {
+        return Ok(None);
+    }
+
+    let head = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+    let base_tree = match &head {
+        Some(commit) => commit.tree(),
+        None => repo
+            .treebuilder(None)
+            .and_then(|b| b.write())
+            .and_then(|oid| repo.find_tree(oid)),
+    }
+    .map_err(|e| format!("Failed to read base tree: {}", e))?;
+
+    let mut update = git2::build::TreeUpdateBuilder::new();
+    for file in &files {
+        let full_path = workdir.join(file);
+        let is_link = std::fs::symlink_metadata(&full_path)
+            .map(|m| m.file_type().is_symlink())
+            .unwrap_or(false);
+        let (oid, mode) = if is_link {
+            let target = std::fs::read_link(&full_path)
+                .map_err(|e| format!("Failed to read link '{}': {}", file, e))?;
+            let oid = repo
+                .blob(target.to_string_lossy().as_bytes())
+                .map_err(|e| format!("Failed to save '{}': {}", file, e))?;
+            (oid, git2::FileMode::Link)
+        } else {
+            let oid = repo
+                .blob_path(&full_path)
+                .map_err(|e| format!("Failed to save '{}': {}", file, e))?;
+            // Keep the exec bit so checking the snapshot out restores it
+            #[cfg(unix)]
+            let executable = {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::metadata(&full_path)
+                    .map(|m| m.permissions().mode() & 0o111 != 0)
+                    .unwrap_or(false)
+            };
+            #[cfg(not(unix))]
+            let executable = false;
+            let mode = if executable {
+                git2::FileMode::BlobExecutable
+            } else {
+                git2::FileMode::Blob
+            };
+            (oid, mode)
+        };
+        update.upsert(file.as_str(), oid, mode);
+    }
+    let tree_oid = update
+        .create_updated(repo, &base_tree)
+        .map_err(|e| format!("Failed to write snapshot tree: {}", e))?;
+    let tree = repo.find_tree(tree_oid).map_err(|e| e.to_string())?;
+
+    let signature = repo
+        .signature()
+        .or_else(|_| Signature::now(PLACEHOLDER_NAME, PLACEHOLDER_EMAIL))
+        .map_err(|e| e.to_string())?;
+    let parents: Vec<&git2::Commit> = head.iter().collect();
+    let message = format!("safety snapshot before {}\n\n{}", action, files.join("\n"));
+    let commit_oid = repo
+        .commit(None, &signature, &signature, &message, &tree, &parents)
+        .map_err(|e| format!("Failed to write snapshot commit: {}", e))?;
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    // The commit id keeps two snapshots taken within the same second apart; an identical
+    // snapshot in the same second gets the same name and commit, so overwriting is a no-op
+    let short = commit_oid.to_string()[..7].to_string();
+    let ref_name = format!("refs/safety/{}/{}-{}", action, timestamp, short);
+    repo.reference(&ref_name, commit_oid, true, &format!("safety snapshot before {}", action))
+        .map_err(|e| format!("Failed to create safety ref: {}", e))?;
+    Ok(Some(ref_name))
+}
+
+/// Writes files saved by `snapshot_worktree_files` back into the working tree. `paths` may name
+/// saved files or directories containing them; empty restores every saved file. Only files the
+/// snapshot actually saved are written, so other working-tree edits are never touched.
+pub fn restore_discarded(repo: &Repository, snapshot_ref: &str, paths: Vec<String>) -> Result<(), String> {
+    if !snapshot_ref.starts_with("refs/safety/") {
+        return Err("Not a safety snapshot".to_string());
+    }
+    let commit = repo
+        .find_reference(snapshot_ref)
+        .and_then(|r| r.peel_to_commit())
+        .map_err(|e| format!("Snapshot not found: {}", e))?;
+    let tree = commit.tree().map_err(|e| e.to_string())?;
+
+    // The saved files are what the snapshot tree changes on top of its parent (HEAD at the
+    // time); the rest is just HEAD, which must not be checked out over newer edits
+    let base = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree().map_err(|e| e.to_string())?),
+        Err(_) => None,
+    };
+    let saved = repo
+        .diff_tree_to_tree(base.as_ref(), Some(&tree), None)
+        .map_err(|e| format!("Failed to read snapshot: {}", e))?;
+    let files: Vec<String> = saved
+        .deltas()
+        .filter_map(|d| d.new_file().path().map(normalize_path))
+        .filter(|file| {
+            paths.is_empty()
+                || paths.iter().any(|p| {
+                    let p = p.trim_end_matches('/');
+                    file == p || file.strip_prefix(p).is_some_and(|rest| rest.starts_with('/'))
+                })
+        })
+        .collect();
+    if files.is_empty() {
+        return Err("None of these paths are saved in the snapshot".to_string());
+    }
+
+    let mut checkout_opts = git2::build::CheckoutBuilder::new();
+    checkout_opts.force().update_index(false);
+    for file in &files {
+        checkout_opts.path(file);
+    }
+    repo.checkout_tree(tree.as_object(), Some(&mut checkout_opts))
+        .map_err(|e| format!("Failed to restore files: {}", e))
+}
+
+/// Discards several paths, snapshotting them together first. A failing path doesn't stop the rest.
+pub fn discard_paths(repo: &Repository, mut paths: Vec<String>) -> Result<DiscardResult, String> {
+    // Discarding a rename also brings back the file under its old name
+    paths.extend(rename_sources(repo, &paths, true, true));
+    let snapshot_ref = snapshot_worktree_files(repo, "discard", &paths)?;
+    let files = paths
+        .into_iter()
+        .map(|path| match discard_path(repo, &path) {
+            Ok(()) => DiscardFileResult {
+                path,
+                discarded: true,
+                error: None,
+            },
+            Err(e) => DiscardFileResult {
+                path,
+                discarded: false,
+                error: Some(e),
+            },
+        })
+        .collect();
+    Ok(DiscardResult { snapshot_ref, files })
+}
+
+/// Discards the selected hunks and lines of a file's unstaged changes, leaving the rest of the
+/// working-tree edits and anything staged untouched. Hunk indices and line numbers refer to
+/// `get_unstaged_diff` (index -> workdir) called with the same `diff_options`, not to the
+/// HEAD -> workdir `get_diff`. The selection is applied as a reverse patch through libgit2, so
+/// the file keeps its filters (line endings, `eol`, smudge) as checkout would write them.
+pub fn discard_hunks(repo: &Repository, options: &DiscardHunksOptions) -> Result<DiscardResult, String> {
+    let diff = unstaged_diff(repo, Some(&options.path), &options.diff_options)?;
+    let idx = diff
+        .deltas()
+        .position(|d| delta_matches(&d, &options.path))
+        .ok_or_else(|| format!("No changes to discard in '{}'", options.path))?;
+    let patch = match git2::Patch::from_diff(&diff, idx).map_err(|e| e.to_string())? {
+        Some(p) => p,
+        None => return Err(format!("No changes to discard in '{}'", options.path)),
+    };
+    if patch.delta().flags().is_binary() {
+        return Err("Cannot discard parts of a binary file".to_string());
+    }
+
+    if let Some(hunk) = options.hunks.iter().find(|h| **h >= patch.num_hunks()) {
+        return Err(format!("Hunk {} does not exist in '{}'", hunk, options.path));
+    }
+    let line_selected = |s: &crate::models::LineSelection, line: &git2::DiffLine| {
+        match line.origin() {
+            '+' => s.new_lineno.is_some() && s.new_lineno == line.new_lineno(),
+            '-' => s.old_lineno.is_some() && s.old_lineno == line.old_lineno(),
+            _ => false,
+        }
+    };
+    let mut matched_lines = vec![false; options.lines.len()];
+    // A line without a trailing newline needs git's marker after it
+    let push_line = |out: &mut String, prefix: char, content: &[u8]| {
+        out.push(prefix);
+        out.push_str(&String::from_utf8_lossy(content));
+        if !content.ends_with(b"\n") {
+            out.push_str("\n\\ No newline at end of file\n");
+        }
+    };
+
+    // Reverse patch: its old side is the working tree, its new side the working tree with the
+    // selected changes undone. Unselected additions stay as context, unselected removals vanish.
+    let path = options.path.trim_end_matches('/');
+    let mut reverse = format!("diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n", path);
+    let mut offset = 0i64;
+    let mut discarded = 0usize;
+    for hunk_idx in 0..patch.num_hunks() {
+        let (hunk, line_count) = patch.hunk(hunk_idx).map_err(|e| e.to_string())?;
+        let mut body = String::new();
+        let (mut old_count, mut new_count, mut hunk_discarded) = (0i64, 0i64, 0usize);
+        for line_idx in 0..line_count {
+            let line = patch
+                .line_in_hunk(hunk_idx, line_idx)
+                .map_err(|e| e.to_string())?;
+            let mut is_selected = options.hunks.contains(&hunk_idx);
+            for (selection, matched) in options.lines.iter().zip(matched_lines.iter_mut()) {
+                if line_selected(selection, &line) {
+                    *matched = true;
+                    is_selected = true;
+                }
+            }
+            match (line.origin(), is_selected) {
+                (' ', _) | ('+', false) => {
+                    push_line(&mut body, ' ', line.content());
+                    old_count += 1;
+                    new_count += 1;
+                }
+                ('+', true) => {
+                    push_line(&mut body, '-', line.content());
+                    old_count += 1;
+                    hunk_discarded += 1;
+                }
+                ('-', true) => {
+                    push_line(&mut body, '+', line.content());
+                    new_count += 1;
+                    hunk_discarded += 1;
+                }
+                _ => {} // unselected removals and end-of-file markers
+            }
+        }
+        if hunk_discarded == 0 {
+            continue;
+        }
+        // An empty side's start is the line before the change, as in the forward diff
+        let old_start = hunk.new_start() as i64;
+        let new_start = match (old_count, new_count) {
+            (0, _) => old_start + offset + 1,
+            (_, 0) => old_start + offset - 1,
+            _ => old_start + offset,
+        };
+        reverse.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n{}",
+            old_start,
+            old_count,
+            new_start.max(0),
+            new_count,
+            body
+        ));
+        offset += new_count - old_count;
+        discarded += hunk_discarded;
+    }
+    // Selections that match no changed line would otherwise be dropped silently
+    if let Some((selection, _)) = options.lines.iter().zip(&matched_lines).find(|(_, m)| !**m) {
+        return Err(format!(
+            "No changed line matches old line {:?} / new line {:?} in '{}'",
+            selection.old_lineno, selection.new_lineno, options.path
+        ));
+    }
+    if discarded == 0 {
+        return Err("No matching hunks or lines to discard".to_string());
+    }
+    let reverse = git2::Diff::from_buffer(reverse.as_bytes())
+        .map_err(|e| format!("Failed to build reverse patch: {}", e))?;
+
+    let snapshot_ref = snapshot_worktree_files(repo, "discard", std::slice::from_ref(&options.path))?;
+    repo.apply(&reverse, git2::ApplyLocation::WorkDir, None)
+        .map_err(|e| format!("Failed to discard changes in '{}': {}", options.path, e))?;
+    Ok(DiscardResult {
+        snapshot_ref,
+        files: vec![DiscardFileResult {
+            path: options.path.clone(),
+            discarded: true,
+            error: None,
+        }],
+    })
+}
+
 
 pub fn create_branch(repo: &Repository, name: &str) -> Result<(), String> {
     if !is_safe_git_arg(name) {
@@ -449,7 +1613,23 @@ pub fn create_branch(repo: &Repository, name: &str) -> Result<(), String> {
     checkout_branch(repo, name)
 }
 
-pub fn get_commit_diff(repo: &Repository, sha: &str) -> Result<Vec<DiffInfo>, String> {
+/// Diffs a commit against one of its parents; `parent` only matters for merge commits.
+pub fn get_commit_diff(
+    repo: &Repository,
+    sha: &str,
+    parent: usize,
+    options: &DiffViewOptions,
+) -> Result<Vec<DiffInfo>, String> {
+    let diff = commit_diff(repo, sha, parent, options)?;
+    diff_to_infos(repo, &diff, options)
+}
+
+fn commit_diff<'r>(
+    repo: &'r Repository,
+    sha: &str,
+    parent: usize,
+    options: &DiffViewOptions,
+) -> Result<git2::Diff<'r>, String> {
     let commit = repo
         .find_commit(git2::Oid::from_str(sha).map_err(|e| e.to_string())?)
         .map_err(|e| format!("Commit not found: {}", e))?;
@@ -458,10 +1638,17 @@ pub fn get_commit_diff(repo: &Repository, sha: &str) -> Result<Vec<DiffInfo>, St
         .tree()
         .map_err(|e| format!("Failed to get tree: {}", e))?;
     let parent_tree = if commit.parent_count() > 0 {
+        if parent >= commit.parent_count() {
+            return Err(format!(
+                "Commit has {} parent(s); parent {} does not exist",
+                commit.parent_count(),
+                parent + 1
+            ));
+        }
         Some(
             commit
-                .parent(0)
-                .map_err(|e| e.to_string())? 
+                .parent(parent)
+                .map_err(|e| e.to_string())?
                 .tree()
                 .map_err(|e| e.to_string())?,
         )
@@ -469,58 +1656,618 @@ pub fn get_commit_diff(repo: &Repository, sha: &str) -> Result<Vec<DiffInfo>, St
         None
     };
 
-    let mut diff_opts = DiffOptions::new();
-    let diff = repo
+    let mut diff_opts = diff_options(options)?;
+    let mut diff = repo
         .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut diff_opts))
         .map_err(|e| format!("Failed to generate diff: {}", e))?;
+    find_similar(&mut diff, options)?;
+    Ok(diff)
+}
 
-    let mut diff_infos = Vec::new();
-    diff.print(git2::DiffFormat::Patch, |delta, _hunk, line| {
-        let path = delta
-            .new_file()
-            .path()
-            .and_then(|p| p.to_str())
-            .unwrap_or("unknown")
-            .to_string();
+fn build_diff<'r>(
+    repo: &'r Repository,
+    source: &DiffSource,
+    options: &DiffViewOptions,
+) -> Result<git2::Diff<'r>, String> {
+    match source.kind.as_str() {
+        "workdir" => workdir_diff(repo, None, options),
+        "staged" => staged_diff(repo, None, options),
+        "unstaged" => unstaged_diff(repo, None, options),
+        "commit" => {
+            let sha = source.sha.as_deref().ok_or("A commit diff needs a sha")?;
+            commit_diff(repo, sha, source.parent, options)
+        }
+        other => Err(format!("Unknown diff source: {}", other)),
+    }
+}
+
+fn delta_path(delta: &git2::DiffDelta) -> String {
+    delta
+        .new_file()
+        .path()
+        .or_else(|| delta.old_file().path())
+        .map(normalize_path)
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Whether `path` names the delta's new or old file.
+fn delta_matches(delta: &git2::DiffDelta, path: &str) -> bool {
+    delta_path(delta) == path || delta.old_file().path().map(normalize_path).as_deref() == Some(path)
+}
+
+/// Size of one side of a delta. Tree entries don't record sizes, so blobs are asked via the
+/// object header, which avoids inflating them.
+fn diff_file_size(odb: Option<&git2::Odb>, file: &git2::DiffFile) -> u64 {
+    if file.size() > 0 || file.id().is_zero() {
+        return file.size();
+    }
+    odb.and_then(|odb| odb.read_header(file.id()).ok())
+        .map(|(size, _)| size as u64)
+        .unwrap_or(0)
+}
+
+/// Whether either side of the delta is above `max_file_size`, so no patch should be built.
+fn exceeds_size_limit(
+    odb: Option<&git2::Odb>,
+    delta: &git2::DiffDelta,
+    options: &DiffViewOptions,
+) -> bool {
+    if options.max_file_size == 0 {
+        return false;
+    }
+    let size = diff_file_size(odb, &delta.old_file()).max(diff_file_size(odb, &delta.new_file()));
+    size > options.max_file_size
+}
+
+/// Summarizes one delta. The patch is None for binary, unchanged and too-large files.
+fn file_summary(
+    delta: &git2::DiffDelta,
+    patch: Option<&git2::Patch>,
+    too_large: bool,
+    options: &DiffViewOptions,
+) -> Result<DiffFileSummary, String> {
+    let (additions, deletions) = match patch {
+        Some(patch) => {
+            let (_, additions, deletions) = patch.line_stats().map_err(|e| e.to_string())?;
+            (additions, deletions)
+        }
+        None => (0, 0),
+    };
+    let mut summary = delta_summary(delta, too_large);
+    summary.additions = Some(additions);
+    summary.deletions = Some(deletions);
+    let too_many_lines =
+        options.max_diff_lines > 0 && additions + deletions > options.max_diff_lines;
+    summary.too_large |= too_many_lines;
+    Ok(summary)
+}
+
+/// Summary from the delta alone, without building its patch: no line counts, and `too_large`
+/// only reflects the file size.
+fn delta_summary(delta: &git2::DiffDelta, too_large: bool) -> DiffFileSummary {
+    let old_path = match delta.status() {
+        git2::Delta::Renamed | git2::Delta::Copied => delta.old_file().path().map(normalize_path),
+        _ => None,
+    };
+    DiffFileSummary {
+        path: delta_path(delta),
+        old_path,
+        status: delta_status_name(delta.status()).to_string(),
+        additions: None,
+        deletions: None,
+        binary: !too_large && delta.flags().is_binary(),
+        too_large,
+        image: image_mime_type(Path::new(&delta_path(delta))).is_some(),
+    }
+}
+
+/// Builds the patch for delta `idx` unless the file is over the size limit.
+fn delta_patch<'r>(
+    diff: &git2::Diff<'r>,
+    idx: usize,
+    odb: Option<&git2::Odb>,
+    options: &DiffViewOptions,
+) -> Result<(Option<git2::Patch<'r>>, bool), String> {
+    let delta = diff.get_delta(idx).ok_or("Delta not found")?;
+    if exceeds_size_limit(odb, &delta, options) {
+        return Ok((None, true));
+    }
+    let patch =
+        git2::Patch::from_diff(diff, idx).map_err(|e| format!("Failed to build patch: {}", e))?;
+    Ok((patch, false))
+}
+
+/// Collects a diff's patch text per file and attaches highlights and LFS details.
+/// Binary and too-large files only carry their metadata.
+fn diff_to_infos(
+    repo: &Repository,
+    diff: &git2::Diff,
+    options: &DiffViewOptions,
+) -> Result<Vec<DiffInfo>, String> {
+    diff_infos_and_summaries(repo, diff, options).map(|(infos, _)| infos)
+}
 
-        let line_content = String::from_utf8_lossy(line.content()).to_string();
-        let prefix = match line.origin() {
-            '+' => "+",
-            '-' => "-",
-            ' ' => " ",
-            _ => "",
+/// `diff_to_infos` plus each file's summary, taken from the same patches so callers that
+/// need both don't build every patch twice.
+fn diff_infos_and_summaries(
+    repo: &Repository,
+    diff: &git2::Diff,
+    options: &DiffViewOptions,
+) -> Result<(Vec<DiffInfo>, Vec<DiffFileSummary>), String> {
+    let odb = repo.odb().ok();
+    let mut diff_infos = Vec::with_capacity(diff.deltas().len());
+    let mut summaries = Vec::with_capacity(diff.deltas().len());
+    for (idx, delta) in diff.deltas().enumerate() {
+        let (mut patch, too_large) = delta_patch(diff, idx, odb.as_ref(), options)?;
+        let summary = file_summary(&delta, patch.as_ref(), too_large, options)?;
+        let lfs = delta_lfs_info(repo, &delta);
+
+        let mut info = DiffInfo {
+            path: summary.path.clone(),
+            additions: summary.additions.unwrap_or(0),
+            deletions: summary.deletions.unwrap_or(0),
+            diff_text: String::new(),
+            lfs: None,
+            highlights: Vec::new(),
+            too_large: summary.too_large,
+            binary: summary.binary,
         };
+        if lfs.is_some() {
+            info.additions = 0;
+            info.deletions = 0;
+            info.lfs = lfs;
+        } else if summary.binary && !options.show_binary {
+            // Listed with `binary` set, but without the "Binary files differ" body
+        } else if !summary.too_large {
+            if let Some(patch) = patch.as_mut() {
+                let buf = patch.to_buf().map_err(|e| format!("Failed to print patch: {}", e))?;
+                info.diff_text = String::from_utf8_lossy(&buf).to_string();
+                info.highlights = patch_highlights(patch, &options.intraline);
+            } else {
+                info.diff_text = delta_header(&delta);
+            }
+        }
+        diff_infos.push(info);
+        summaries.push(summary);
+    }
+    Ok((diff_infos, summaries))
+}
 
-        if let Some(info) = diff_infos
-            .iter_mut()
-            .find(|i: &&mut DiffInfo| i.path == path)
-        {
-            info.diff_text
-                .push_str(&format!("{}{}", prefix, line_content));
-            match line.origin() {
-                '+' => info.additions += 1,
-                '-' => info.deletions += 1,
-                _ => {} 
+/// Header for deltas without a patch: binary files, pure renames and mode changes.
+fn delta_header(delta: &git2::DiffDelta) -> String {
+    let old_path = delta.old_file().path().map(normalize_path).unwrap_or_default();
+    let path = delta_path(delta);
+    let mut header = format!("diff --git a/{} b/{}\n", old_path, path);
+    match delta.status() {
+        git2::Delta::Renamed => header.push_str(&format!("rename from {}\nrename to {}\n", old_path, path)),
+        git2::Delta::Copied => header.push_str(&format!("copy from {}\ncopy to {}\n", old_path, path)),
+        _ => {}
+    }
+    if delta.flags().is_binary() {
+        header.push_str(&format!("Binary files a/{} and b/{} differ\n", old_path, path));
+    }
+    header
+}
+
+/// First phase of a lazy diff: every changed file with its status and the overall stats, no
+/// content.
+pub fn get_diff_files(
+    repo: &Repository,
+    source: &DiffSource,
+    options: &DiffViewOptions,
+) -> Result<DiffFileList, String> {
+    let diff = build_diff(repo, source, options)?;
+    let odb = repo.odb().ok();
+    // No patches here: per-file line counts and the max_diff_lines check wait for get_file_diff
+    let files = diff
+        .deltas()
+        .map(|delta| delta_summary(&delta, exceeds_size_limit(odb.as_ref(), &delta, options)))
+        .collect();
+    let diff_stats = diff.stats().map_err(|e| format!("Failed to get diff stats: {}", e))?;
+    let stats = DiffStats {
+        files_changed: diff_stats.files_changed(),
+        insertions: diff_stats.insertions(),
+        deletions: diff_stats.deletions(),
+    };
+    Ok(DiffFileList { stats, files })
+}
+
+/// Second phase: the hunks of one file from `get_diff_files`, matched by new or old path.
+/// Binary, LFS and too-large files come back without hunks.
+pub fn get_file_diff(
+    repo: &Repository,
+    source: &DiffSource,
+    path: &str,
+    options: &DiffViewOptions,
+) -> Result<FileDiff, String> {
+    // Diffing everything (without patches) keeps renames paired, which a pathspec would break
+    let diff = build_diff(repo, source, options)?;
+    let idx = diff
+        .deltas()
+        .position(|d| delta_matches(&d, path))
+        .ok_or_else(|| format!("'{}' is not part of this diff", path))?;
+    let delta = diff.get_delta(idx).ok_or("Delta not found")?;
+
+    let odb = repo.odb().ok();
+    let (patch, too_large) = delta_patch(&diff, idx, odb.as_ref(), options)?;
+    let file = file_summary(&delta, patch.as_ref(), too_large, options)?;
+    let lfs = delta_lfs_info(repo, &delta);
+
+    let mut hunks = Vec::new();
+    let mut highlights = Vec::new();
+    if let (Some(patch), false, None) = (patch.as_ref(), file.too_large, &lfs) {
+        for hunk_idx in 0..patch.num_hunks() {
+            let (hunk, line_count) = patch.hunk(hunk_idx).map_err(|e| e.to_string())?;
+            let mut lines = Vec::with_capacity(line_count);
+            for line_idx in 0..line_count {
+                let line = patch
+                    .line_in_hunk(hunk_idx, line_idx)
+                    .map_err(|e| e.to_string())?;
+                lines.push(DiffLineInfo {
+                    origin: line.origin().to_string(),
+                    content: String::from_utf8_lossy(line.content())
+                        .trim_end_matches(['\n', '\r'])
+                        .to_string(),
+                    old_lineno: line.old_lineno(),
+                    new_lineno: line.new_lineno(),
+                });
             }
-        } else {
-            diff_infos.push(DiffInfo {
-                path,
-                diff_text: format!("{}{}", prefix, line_content),
-                additions: if line.origin() == '+' { 1 } else { 0 },
-                deletions: if line.origin() == '-' { 1 } else { 0 },
+            hunks.push(DiffHunkInfo {
+                header: String::from_utf8_lossy(hunk.header()).trim_end().to_string(),
+                old_start: hunk.old_start(),
+                old_lines: hunk.old_lines(),
+                new_start: hunk.new_start(),
+                new_lines: hunk.new_lines(),
+                lines,
             });
         }
-        true
+        highlights = patch_highlights(patch, &options.intraline);
+    }
+    Ok(FileDiff {
+        file,
+        hunks,
+        highlights,
+        lfs,
     })
-    .map_err(|e| format!("Failed to parse diff: {}", e))?;
+}
+
+/// Changes staged for the next commit (HEAD -> index).
+pub fn get_staged_diff(
+    repo: &Repository,
+    path: Option<&str>,
+    options: &DiffViewOptions,
+) -> Result<Vec<DiffInfo>, String> {
+    let diff = staged_diff(repo, path, options)?;
+    diff_to_infos(repo, &diff, options)
+}
+
+fn staged_diff<'r>(
+    repo: &'r Repository,
+    path: Option<&str>,
+    options: &DiffViewOptions,
+) -> Result<git2::Diff<'r>, String> {
+    let head_tree = repo.head().ok().and_then(|h| h.peel_to_tree().ok());
+    let mut opts = diff_options(options)?;
+    if let Some(p) = path {
+        opts.pathspec(p);
+    }
+    // An unborn branch diffs against the empty tree, so everything in the index shows as added
+    let mut diff = repo
+        .diff_tree_to_index(head_tree.as_ref(), None, Some(&mut opts))
+        .map_err(|e| format!("Failed to get diff (tree to index): {}", e))?;
+    find_similar(&mut diff, options)?;
+    Ok(diff)
+}
+
+/// Changes not yet staged (index -> workdir), including the content of untracked files.
+pub fn get_unstaged_diff(
+    repo: &Repository,
+    path: Option<&str>,
+    options: &DiffViewOptions,
+) -> Result<Vec<DiffInfo>, String> {
+    let diff = unstaged_diff(repo, path, options)?;
+    diff_to_infos(repo, &diff, options)
+}
+
+fn unstaged_diff<'r>(
+    repo: &'r Repository,
+    path: Option<&str>,
+    options: &DiffViewOptions,
+) -> Result<git2::Diff<'r>, String> {
+    let mut opts = diff_options(options)?;
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .show_untracked_content(true);
+    if let Some(p) = path {
+        opts.pathspec(p);
+    }
+    let mut diff = repo
+        .diff_index_to_workdir(None, Some(&mut opts))
+        .map_err(|e| format!("Failed to get diff (index to workdir): {}", e))?;
+    find_similar(&mut diff, options)?;
+    Ok(diff)
+}
+
+/// Combined diff of a merge commit against all its parents, which libgit2 can't produce.
+/// `condensed` (`--cc`) keeps only hunks that differ from every parent: the conflict resolutions
+/// and any other changes made while merging.
+/// `git diff` flags for the settings `diff_options` gives libgit2, for diffs only the git CLI
+/// can produce.
+fn diff_cli_args(options: &DiffViewOptions) -> Result<Vec<String>, String> {
+    // Reject unknown modes and algorithms exactly like the libgit2 diffs do
+    diff_options(options)?;
+    let mut args = vec![
+        format!("-U{}", options.context_lines),
+        format!("--inter-hunk-context={}", options.interhunk_lines),
+    ];
+    match options.ignore_whitespace.as_str() {
+        "all" => args.push("-w".to_string()),
+        "change" => args.push("-b".to_string()),
+        "eol" => args.push("--ignore-space-at-eol".to_string()),
+        _ => {}
+    }
+    if options.ignore_blank_lines {
+        args.push("--ignore-blank-lines".to_string());
+    }
+    match options.algorithm.as_str() {
+        "minimal" => args.push("--minimal".to_string()),
+        "patience" => args.push("--patience".to_string()),
+        _ => {}
+    }
+    Ok(args)
+}
+
+pub fn get_combined_diff(
+    repo: &Repository,
+    sha: &str,
+    condensed: bool,
+    options: &DiffViewOptions,
+) -> Result<Vec<DiffInfo>, String> {
+    let commit = repo
+        .find_commit(git2::Oid::from_str(sha).map_err(|e| e.to_string())?)
+        .map_err(|e| format!("Commit not found: {}", e))?;
+    let parents = commit.parent_count();
+    if parents < 2 {
+        return Err("Not a merge commit".to_string());
+    }
+
+    let cwd = repo.workdir().unwrap_or(repo.path());
+    let sha = commit.id().to_string();
+    let mode = if condensed { "--cc" } else { "-c" };
+    let cli_args = diff_cli_args(options)?;
+    let mut args = vec!["show", "--no-color", "--no-ext-diff", "--format=", mode];
+    args.extend(cli_args.iter().map(String::as_str));
+    args.push(&sha);
+    // Raw stdout rather than run_git_command, whose trimming drops the patch's final newline
+    let result = Command::new("git")
+        .args(&args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .current_dir(cwd)
+        .output()
+        .map_err(|e| format!("Failed to execute git command: {}", e))?;
+    if !result.status.success() {
+        return Err(format!(
+            "Failed to get combined diff: {}",
+            String::from_utf8_lossy(&result.stderr).trim()
+        ));
+    }
+    let output = String::from_utf8_lossy(&result.stdout);
+
+    let mut diff_infos: Vec<DiffInfo> = Vec::new();
+    let mut in_hunk = false;
+    for line in output.lines() {
+        let header = line
+            .strip_prefix("diff --cc ")
+            .or_else(|| line.strip_prefix("diff --combined "));
+        if let Some(path) = header {
+            diff_infos.push(DiffInfo {
+                path: path.to_string(),
+                additions: 0,
+                deletions: 0,
+                diff_text: String::new(),
+                lfs: None,
+                highlights: Vec::new(),
+                too_large: false,
+                binary: false,
+            });
+            in_hunk = false;
+        }
+        let info = match diff_infos.last_mut() {
+            Some(info) => info,
+            None => continue,
+        };
+        info.diff_text.push_str(line);
+        info.diff_text.push('\n');
+
+        if line.starts_with("@@@") {
+            in_hunk = true;
+        } else if !in_hunk && line.starts_with("Binary files ") {
+            info.binary = true;
+        } else if in_hunk {
+            // One marker column per parent
+            let markers: String = line.chars().take(parents).collect();
+            if markers.contains('+') {
+                info.additions += 1;
+            } else if markers.contains('-') {
+                info.deletions += 1;
+            }
+        }
+    }
 
+    // Same limits as the libgit2 diffs: metadata only for big files and hidden binary bodies
+    let odb = repo.odb().ok();
+    let mut trees = vec![commit.tree().map_err(|e| e.to_string())?];
+    for parent in commit.parents() {
+        trees.push(parent.tree().map_err(|e| e.to_string())?);
+    }
+    let blob_too_large = |path: &str| {
+        options.max_file_size > 0
+            && trees.iter().any(|tree| {
+                tree.get_path(Path::new(path))
+                    .ok()
+                    .and_then(|entry| odb.as_ref()?.read_header(entry.id()).ok())
+                    .is_some_and(|(size, _)| size as u64 > options.max_file_size)
+            })
+    };
+    for info in &mut diff_infos {
+        let too_many_lines =
+            options.max_diff_lines > 0 && info.additions + info.deletions > options.max_diff_lines;
+        if too_many_lines || blob_too_large(&info.path) {
+            info.too_large = true;
+            info.diff_text.clear();
+        } else if info.binary && !options.show_binary {
+            info.diff_text.clear();
+        }
+    }
     Ok(diff_infos)
 }
 
-pub fn create_commit(repo: &Repository, message: &str) -> Result<String, String> {
+/// Resolves the common git directory, which differs from `repo.path()` for linked worktrees.
+pub fn common_git_dir(repo: &Repository) -> PathBuf {
+    let git_dir = repo.path();
+    match std::fs::read_to_string(git_dir.join("commondir")) {
+        Ok(content) => {
+            let common = PathBuf::from(content.trim());
+            if common.is_absolute() {
+                common
+            } else {
+                git_dir.join(common)
+            }
+        }
+        Err(_) => git_dir.to_path_buf(),
+    }
+}
+
+/// Resolves the hooks directory, respecting `core.hooksPath`.
+fn hooks_dir(repo: &Repository) -> PathBuf {
+    if let Ok(config) = repo.config() {
+        if let Ok(hooks_path) = config.get_path("core.hooksPath") {
+            if hooks_path.is_absolute() {
+                return hooks_path;
+            }
+            // Relative hooks paths are resolved the way git does: from the top of the worktree
+            return repo.workdir().unwrap_or_else(|| repo.path()).join(hooks_path);
+        }
+    }
+    common_git_dir(repo).join("hooks")
+}
+
+/// Runs a repository hook if it exists and is executable.
+/// Returns the hook's combined output on failure so callers can surface it to the user.
+fn run_hook(repo: &Repository, name: &str, args: &[&str]) -> Result<(), String> {
+    let hook_path = hooks_dir(repo).join(name);
+    if !hook_path.is_file() {
+        return Ok(());
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&hook_path)
+            .map(|m| m.permissions().mode())
+            .unwrap_or(0);
+        if mode & 0o111 == 0 {
+            return Ok(());
+        }
+    }
+
+    // Hooks are usually shell scripts; Windows has no shebang support so go through sh
+    #[cfg(windows)]
+    let mut command = {
+        let mut c = Command::new("sh");
+        c.arg(&hook_path);
+        c
+    };
+    #[cfg(not(windows))]
+    let mut command = Command::new(&hook_path);
+
+    command.args(args);
+    command.env("GIT_TERMINAL_PROMPT", "0");
+    command.env("GIT_INDEX_FILE", repo.path().join("index"));
+    command.current_dir(repo.workdir().unwrap_or_else(|| repo.path()));
+
+    let output = command
+        .output()
+        .map_err(|e| format!("Failed to run {} hook: {}", name, e))?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    let captured = [stdout, stderr]
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Err(if captured.is_empty() {
+        format!("{} hook failed with status: {}", name, output.status)
+    } else {
+        format!("{} hook failed:\n{}", name, captured)
+    })
+}
+
+/// Runs `pre-commit`, `prepare-commit-msg` and `commit-msg` in the same order as `git commit`.
+/// `source` is the `prepare-commit-msg` source argument list (e.g. `["commit", "HEAD"]` when amending).
+/// `no_verify` skips `pre-commit` and `commit-msg` like `git commit --no-verify`.
+/// Returns the message as left in `COMMIT_EDITMSG` by the hooks, cleaned up like git does:
+/// comment lines the hooks added are dropped, along with trailing whitespace and extra blank lines.
+fn run_pre_commit_hooks(
+    repo: &Repository,
+    message: &str,
+    source: &[&str],
+    no_verify: bool,
+) -> Result<String, String> {
+    if !no_verify {
+        run_hook(repo, "pre-commit", &[])?;
+    }
+
+    let msg_path = repo.path().join("COMMIT_EDITMSG");
+    std::fs::write(&msg_path, message)
+        .map_err(|e| format!("Failed to write commit message file: {}", e))?;
+    let msg_arg = msg_path.to_string_lossy().to_string();
+
+    let mut prepare_args = vec![msg_arg.as_str()];
+    prepare_args.extend_from_slice(source);
+    run_hook(repo, "prepare-commit-msg", &prepare_args)?;
+    if !no_verify {
+        run_hook(repo, "commit-msg", &[msg_arg.as_str()])?;
+    }
+
+    let hooked_message = std::fs::read_to_string(&msg_path)
+        .map_err(|e| format!("Failed to read commit message file: {}", e))?;
+    // Lines starting with '#' that the user wrote themselves (issue references) are kept
+    let original: std::collections::HashSet<&str> = message.lines().collect();
+    let kept: String = hooked_message
+        .lines()
+        .filter(|line| !line.starts_with('#') || original.contains(line))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    let final_message = git2::message_prettify(kept, None)
+        .map_err(|e| format!("Failed to clean up commit message: {}", e))?;
+    if final_message.trim().is_empty() {
+        return Err("Aborting commit due to empty commit message".to_string());
+    }
+    Ok(final_message)
+}
+
+pub fn create_commit(
+    repo: &Repository,
+    message: &str,
+    signature: &Signature,
+    skip_hooks: bool,
+) -> Result<String, String> {
+    let message = run_pre_commit_hooks(repo, message, &["message"], skip_hooks)?;
+
     let mut index = repo
         .index()
         .map_err(|e| format!("Failed to get index: {}", e))?;
+    // pre-commit may have staged files through the git CLI
+    index
+        .read(true)
+        .map_err(|e| format!("Failed to read index: {}", e))?;
 
     let tree_id = index
         .write_tree()
@@ -530,11 +2277,6 @@ pub fn create_commit(repo: &Repository, message: &str) -> Result<String, String>
         .find_tree(tree_id)
         .map_err(|e| format!("Failed to find tree: {}", e))?;
 
-    let signature = repo
-        .signature()
-        .or_else(|_| Signature::now("User", "user@example.com"))
-        .map_err(|e| format!("Failed to create signature: {}", e))?;
-
     let head = repo.head().ok();
     let parent_commit = head.as_ref().and_then(|h| h.peel_to_commit().ok());
 
@@ -548,23 +2290,272 @@ pub fn create_commit(repo: &Repository, message: &str) -> Result<String, String>
     let commit_id = repo
         .commit(
             Some("HEAD"),
-            &signature,
-            &signature,
-            message,
+            signature,
+            signature,
+            &message,
             &tree,
             &parent_refs,
         )
         .map_err(|e| format!("Failed to create commit: {}", e))?;
 
+    // post-commit cannot affect the outcome, mirroring git
+    let _ = run_hook(repo, "post-commit", &[]);
+
     Ok(commit_id.to_string())
 }
 
-pub fn get_branches(repo: &Repository) -> Result<Vec<BranchInfo>, String> {
-    let branches = repo
-        .branches(Some(BranchType::Local))
-        .map_err(|e| format!("Failed to get branches: {}", e))?;
-
-    let head = repo.head().ok();
+/// Reads the file configured as `commit.template`, if any.
+pub fn get_commit_template(repo: &Repository) -> Result<Option<String>, String> {
+    let config = repo
+        .config()
+        .map_err(|e| format!("Failed to read config: {}", e))?;
+    let template_path = match config.get_path("commit.template") {
+        Ok(p) => p,
+        Err(_) => return Ok(None),
+    };
+    let template_path = if template_path.is_absolute() {
+        template_path
+    } else {
+        repo.workdir().unwrap_or_else(|| repo.path()).join(template_path)
+    };
+
+    std::fs::read_to_string(&template_path)
+        .map(Some)
+        .map_err(|e| format!("Failed to read commit template '{}': {}", template_path.display(), e))
+}
+
+/// Appends trailers (and optionally a Signed-off-by line) to a commit message.
+/// Trailers already present with the same value are not repeated.
+pub fn apply_trailers(
+    message: &str,
+    trailers: &[CommitTrailer],
+    sign_off: Option<&CommitIdentity>,
+) -> Result<String, String> {
+    let existing: Vec<(String, String)> = git2::message_trailers_strs(message)
+        .map(|t| t.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect())
+        .unwrap_or_default();
+
+    let sign_off_trailer = sign_off.map(|identity| CommitTrailer {
+        key: "Signed-off-by".to_string(),
+        value: format!("{} <{}>", identity.name, identity.email),
+    });
+
+    let mut to_add: Vec<(String, String)> = Vec::new();
+    for trailer in trailers.iter().chain(sign_off_trailer.iter()) {
+        let key = trailer.key.trim();
+        let value = trailer.value.trim();
+        if key.is_empty() || value.is_empty() {
+            continue;
+        }
+        if key.contains(':') || key.contains(char::is_whitespace) || value.contains('\n') {
+            return Err(format!("Invalid trailer '{}: {}'", key, value));
+        }
+        let duplicate = existing
+            .iter()
+            .chain(to_add.iter())
+            .any(|(k, v)| k.eq_ignore_ascii_case(key) && v == value);
+        if !duplicate {
+            to_add.push((key.to_string(), value.to_string()));
+        }
+    }
+
+    if to_add.is_empty() {
+        return Ok(message.to_string());
+    }
+
+    let body = message.trim_end();
+    // Join an existing trailer block, otherwise start a new paragraph
+    let separator = if existing.is_empty() { "\n\n" } else { "\n" };
+    let block = to_add
+        .iter()
+        .map(|(k, v)| format!("{}: {}", k, v))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(format!("{}{}{}\n", body, separator, block))
+}
+
+/// App-specific lint config file. commitlint's own `.commitlintrc*` files use a different
+/// schema (and often YAML or JS), so they are deliberately not read.
+const COMMIT_LINT_CONFIG_FILE: &str = ".cyberpunk-commitlint.json";
+
+/// Loads the repository's commit lint rules, falling back to defaults when no config file exists.
+pub fn load_commit_lint_config(repo: &Repository) -> Result<CommitLintConfig, String> {
+    let workdir = match repo.workdir() {
+        Some(w) => w,
+        None => return Ok(CommitLintConfig::default()),
+    };
+
+    let config_path = workdir.join(COMMIT_LINT_CONFIG_FILE);
+    if !config_path.is_file() {
+        return Ok(CommitLintConfig::default());
+    }
+    let content = std::fs::read_to_string(&config_path)
+        .map_err(|e| format!("Failed to read {}: {}", COMMIT_LINT_CONFIG_FILE, e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Invalid {}: {}", COMMIT_LINT_CONFIG_FILE, e))
+}
+
+/// Splits a Conventional Commits subject into (type, scope, description).
+fn parse_conventional_subject(subject: &str) -> Option<(&str, Option<&str>, &str)> {
+    let (header, description) = subject.split_once(": ")?;
+    let header = header.strip_suffix('!').unwrap_or(header);
+    let (commit_type, scope) = match header.split_once('(') {
+        Some((t, rest)) => (t, Some(rest.strip_suffix(')')?)),
+        None => (header, None),
+    };
+    if commit_type.is_empty() || !commit_type.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return None;
+    }
+    Some((commit_type, scope, description))
+}
+
+pub fn lint_commit_message(message: &str, config: &CommitLintConfig) -> CommitLintResult {
+    let mut violations = Vec::new();
+    let mut violation = |rule: &str, message: String, line: usize, severity: &str| {
+        violations.push(CommitLintViolation {
+            rule: rule.to_string(),
+            message,
+            line,
+            severity: severity.to_string(),
+        });
+    };
+
+    let lines: Vec<&str> = message.lines().collect();
+    let subject = lines.first().copied().unwrap_or("").trim_end();
+
+    if subject.trim().is_empty() {
+        violation("subject-empty", "Subject line is empty".to_string(), 1, "error");
+    } else if subject.chars().count() > config.subject_max_length {
+        violation(
+            "subject-max-length",
+            format!("Subject is {} characters, limit is {}", subject.chars().count(), config.subject_max_length),
+            1,
+            "error",
+        );
+    }
+
+    if config.require_blank_second_line && lines.len() > 1 && !lines[1].trim().is_empty() {
+        violation("body-leading-blank", "Second line must be blank".to_string(), 2, "error");
+    }
+
+    if let Some(width) = config.body_wrap_width {
+        for (i, line) in lines.iter().enumerate().skip(2) {
+            // Unbreakable content such as URLs cannot be wrapped
+            if line.chars().count() > width && line.trim().contains(char::is_whitespace) {
+                violation(
+                    "body-max-line-length",
+                    format!("Line exceeds {} characters", width),
+                    i + 1,
+                    "warning",
+                );
+            }
+        }
+    }
+
+    if config.conventional && !subject.trim().is_empty() {
+        match parse_conventional_subject(subject) {
+            None => violation(
+                "conventional-format",
+                "Subject must follow 'type(scope): description'".to_string(),
+                1,
+                "error",
+            ),
+            Some((commit_type, scope, description)) => {
+                if !config.types.is_empty() && !config.types.iter().any(|t| t == commit_type) {
+                    violation(
+                        "type-enum",
+                        format!("Type '{}' must be one of: {}", commit_type, config.types.join(", ")),
+                        1,
+                        "error",
+                    );
+                }
+                match scope {
+                    None if config.require_scope => {
+                        violation("scope-empty", "A scope is required".to_string(), 1, "error")
+                    }
+                    Some(scope) if !config.scopes.is_empty() && !config.scopes.iter().any(|s| s == scope) => {
+                        violation(
+                            "scope-enum",
+                            format!("Scope '{}' must be one of: {}", scope, config.scopes.join(", ")),
+                            1,
+                            "error",
+                        )
+                    }
+                    _ => {}
+                }
+                if description.trim().is_empty() {
+                    violation("subject-empty", "Description after the type is empty".to_string(), 1, "error");
+                }
+            }
+        }
+    }
+
+    let blocking = config.enforce && violations.iter().any(|v| v.severity == "error");
+    CommitLintResult {
+        violations,
+        enforced: config.enforce,
+        blocking,
+    }
+}
+
+/// Rejects the message when the repository enforces lint rules and the message has errors.
+pub fn enforce_commit_lint(repo: &Repository, message: &str) -> Result<(), String> {
+    let config = load_commit_lint_config(repo)?;
+    let result = lint_commit_message(message, &config);
+    if !result.blocking {
+        return Ok(());
+    }
+
+    let errors: Vec<String> = result
+        .violations
+        .iter()
+        .filter(|v| v.severity == "error")
+        .map(|v| format!("line {}: {}", v.line, v.message))
+        .collect();
+    Err(format!("Commit message rejected by lint rules: {}", errors.join("; ")))
+}
+
+/// Lists people who authored commits reachable from HEAD, most active first.
+pub fn get_coauthor_candidates(repo: &Repository, limit: usize) -> Result<Vec<CoAuthor>, String> {
+    let mut revwalk = repo
+        .revwalk()
+        .map_err(|e| format!("Failed to create revwalk: {}", e))?;
+    if revwalk.push_head().is_err() {
+        return Ok(Vec::new()); // unborn branch
+    }
+
+    let mut authors: Vec<CoAuthor> = Vec::new();
+    for oid in revwalk.take(limit) {
+        let oid = oid.map_err(|e| format!("Failed to get OID: {}", e))?;
+        let commit = repo
+            .find_commit(oid)
+            .map_err(|e| format!("Failed to find commit: {}", e))?;
+        let author = commit.author();
+        let (name, email) = match (author.name(), author.email()) {
+            (Some(n), Some(e)) if !e.is_empty() => (n, e),
+            _ => continue,
+        };
+
+        match authors.iter_mut().find(|a| a.email.eq_ignore_ascii_case(email)) {
+            Some(existing) => existing.commit_count += 1,
+            None => authors.push(CoAuthor {
+                name: name.to_string(),
+                email: email.to_string(),
+                commit_count: 1,
+            }),
+        }
+    }
+
+    authors.sort_by(|a, b| b.commit_count.cmp(&a.commit_count).then_with(|| a.name.cmp(&b.name)));
+    Ok(authors)
+}
+
+pub fn get_branches(repo: &Repository) -> Result<Vec<BranchInfo>, String> {
+    let branches = repo
+        .branches(Some(BranchType::Local))
+        .map_err(|e| format!("Failed to get branches: {}", e))?;
+
+    let head = repo.head().ok();
     let current_branch_name = head
         .as_ref()
         .and_then(|h| h.shorthand())
@@ -609,21 +2600,43 @@ pub fn checkout_branch(repo: &Repository, name: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// Returns the OID the current branch's upstream points to, if it has one.
+fn head_upstream_oid(repo: &Repository) -> Option<git2::Oid> {
+    let head = repo.head().ok()?;
+    if !head.is_branch() {
+        return None;
+    }
+    let upstream = repo.branch_upstream_name(head.name()?).ok()?;
+    repo.find_reference(upstream.as_str()?).ok()?.target()
+}
+
+/// A commit is pushed if the upstream can reach it.
+fn is_reachable_from(repo: &Repository, upstream_oid: Option<git2::Oid>, oid: git2::Oid) -> bool {
+    match upstream_oid {
+        Some(u_oid) => u_oid == oid || repo.graph_descendant_of(u_oid, oid).unwrap_or(false),
+        None => false,
+    }
+}
+
+pub fn is_head_pushed(repo: &Repository) -> Result<bool, String> {
+    let head = repo
+        .head()
+        .map_err(|e| format!("Failed to get HEAD: {}", e))?;
+    let oid = head.target().ok_or("HEAD has no target")?;
+    Ok(is_reachable_from(repo, head_upstream_oid(repo), oid))
+}
+
+pub fn get_head_message(repo: &Repository) -> Result<String, String> {
+    let commit = repo
+        .head()
+        .and_then(|h| h.peel_to_commit())
+        .map_err(|e| format!("Failed to get HEAD commit: {}", e))?;
+    Ok(commit.message().unwrap_or("").to_string())
+}
+
 pub fn get_commit_history(repo: &Repository, limit: usize) -> Result<Vec<CommitInfo>, String> {
-    let head = repo.head().ok();
-    
     // Get upstream OID to check for pushed status
-    let upstream_oid = head.as_ref().and_then(|h| {
-        if h.is_branch() {
-            h.name().and_then(|name| {
-                repo.branch_upstream_name(name).ok().and_then(|upstream| {
-                    upstream.as_str().and_then(|u_name| repo.find_reference(u_name).ok()).and_then(|r| r.target())
-                })
-            })
-        } else {
-            None
-        }
-    });
+    let upstream_oid = head_upstream_oid(repo);
 
     let mut revwalk = repo
         .revwalk()
@@ -645,81 +2658,362 @@ pub fn get_commit_history(repo: &Repository, limit: usize) -> Result<Vec<CommitI
             .find_commit(oid)
             .map_err(|e| format!("Failed to find commit: {}", e))?;
 
-        // Logic: if upstream can reach this commit, it is pushed.
-        let is_pushed = if let Some(u_oid) = upstream_oid {
-            repo.graph_descendant_of(u_oid, oid).unwrap_or(false) || u_oid == oid
+        let is_pushed = is_reachable_from(repo, upstream_oid, oid);
+        commits.push(commit_info(&commit, is_pushed));
+    }
+
+    Ok(commits)
+}
+
+/// Case-folds text for a case-insensitive search.
+fn search_fold(text: &str, case_sensitive: bool) -> std::borrow::Cow<'_, str> {
+    if case_sensitive {
+        std::borrow::Cow::Borrowed(text)
+    } else {
+        std::borrow::Cow::Owned(text.to_lowercase())
+    }
+}
+
+/// Whether the commit's changes match a pickaxe search, compared against the first parent
+/// like `git log -S`/`-G`. Merge commits are skipped, as git does without `-m`.
+fn commit_diff_matches(
+    repo: &Repository,
+    commit: &git2::Commit,
+    query: &str,
+    pattern: Option<&regex::Regex>,
+    case_sensitive: bool,
+) -> Result<bool, String> {
+    if commit.parent_count() > 1 {
+        return Ok(false);
+    }
+    let tree = commit.tree().map_err(|e| e.to_string())?;
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree().map_err(|e| e.to_string())?),
+        Err(_) => None,
+    };
+    let diff = repo
+        .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+        .map_err(|e| format!("Failed to generate diff: {}", e))?;
+
+    for (idx, delta) in diff.deltas().enumerate() {
+        if let Some(pattern) = pattern {
+            let Some(patch) = git2::Patch::from_diff(&diff, idx).map_err(|e| e.to_string())? else {
+                continue;
+            };
+            for hunk_idx in 0..patch.num_hunks() {
+                let line_count = patch.num_lines_in_hunk(hunk_idx).map_err(|e| e.to_string())?;
+                for line_idx in 0..line_count {
+                    let line = patch.line_in_hunk(hunk_idx, line_idx).map_err(|e| e.to_string())?;
+                    if matches!(line.origin(), '+' | '-')
+                        && pattern.is_match(&String::from_utf8_lossy(line.content()))
+                    {
+                        return Ok(true);
+                    }
+                }
+            }
         } else {
-            false
+            if delta.flags().is_binary() {
+                continue;
+            }
+            let count = |file: git2::DiffFile| -> usize {
+                if file.id().is_zero() {
+                    return 0;
+                }
+                repo.find_blob(file.id())
+                    .ok()
+                    .filter(|blob| !blob.is_binary())
+                    .map(|blob| {
+                        let text = String::from_utf8_lossy(blob.content());
+                        search_fold(&text, case_sensitive).matches(query).count()
+                    })
+                    .unwrap_or(0)
+            };
+            if count(delta.old_file()) != count(delta.new_file()) {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Searches history for commits whose message, author, committer, SHA prefix or (optionally)
+/// diff matches the query, newest first. Matches are handed to `on_page` in batches of
+/// `page_size` together with the number of commits scanned; `is_cancelled` is polled per commit.
+pub fn search_commits(
+    repo: &Repository,
+    options: &CommitSearchOptions,
+    is_cancelled: impl Fn() -> bool,
+    mut on_page: impl FnMut(Vec<CommitSearchMatch>, usize),
+) -> Result<CommitSearchSummary, String> {
+    if options.query.is_empty() {
+        return Err("Search query is empty".to_string());
+    }
+    let query = search_fold(&options.query, options.case_sensitive);
+    let sha_query = options.query.to_ascii_lowercase();
+    let sha_query = (options.sha && sha_query.chars().all(|c| c.is_ascii_hexdigit()))
+        .then_some(sha_query);
+    let pattern = match options.diff.as_str() {
+        "none" | "string" => None,
+        "regex" => Some(
+            regex::RegexBuilder::new(&options.query)
+                .case_insensitive(!options.case_sensitive)
+                .build()
+                .map_err(|e| format!("Invalid search pattern: {}", e))?,
+        ),
+        other => return Err(format!("Unknown diff search mode: {}", other)),
+    };
+
+    let mut revwalk = repo
+        .revwalk()
+        .map_err(|e| format!("Failed to create revwalk: {}", e))?;
+    revwalk
+        .set_sorting(git2::Sort::TIME)
+        .map_err(|e| format!("Failed to sort revwalk: {}", e))?;
+    if options.all_refs {
+        // Only user-facing refs; internal ones such as refs/safety/* snapshots stay hidden
+        for glob in ["refs/heads/*", "refs/remotes/*", "refs/tags/*"] {
+            let references = repo
+                .references_glob(glob)
+                .map_err(|e| format!("Failed to list references: {}", e))?;
+            for reference in references.flatten() {
+                // Tags of trees or blobs have no history to search
+                if let Ok(commit) = reference.peel_to_commit() {
+                    revwalk
+                        .push(commit.id())
+                        .map_err(|e| format!("Failed to walk references: {}", e))?;
+                }
+            }
+        }
+    }
+    if !options.all_refs || repo.head_detached().unwrap_or(false) {
+        revwalk
+            .push_head()
+            .map_err(|e| format!("Failed to push HEAD: {}", e))?;
+    }
+
+    let upstream_oid = head_upstream_oid(repo);
+    let page_size = options.page_size.max(1);
+    let mut page = Vec::new();
+    let mut summary = CommitSearchSummary {
+        total: 0,
+        scanned: 0,
+        cancelled: false,
+    };
+    for oid in revwalk {
+        if is_cancelled() {
+            summary.cancelled = true;
+            break;
+        }
+        let oid = oid.map_err(|e| format!("Failed to get OID: {}", e))?;
+        let commit = repo
+            .find_commit(oid)
+            .map_err(|e| format!("Failed to find commit: {}", e))?;
+        summary.scanned += 1;
+
+        let contains = |text: Option<&str>| {
+            text.is_some_and(|t| search_fold(t, options.case_sensitive).contains(query.as_ref()))
         };
+        let mut matched = Vec::new();
+        if options.messages && contains(commit.message()) {
+            matched.push("message".to_string());
+        }
+        let (author, committer) = (commit.author(), commit.committer());
+        if options.authors && (contains(author.name()) || contains(author.email())) {
+            matched.push("author".to_string());
+        }
+        if options.committers && (contains(committer.name()) || contains(committer.email())) {
+            matched.push("committer".to_string());
+        }
+        if sha_query.as_ref().is_some_and(|q| oid.to_string().starts_with(q.as_str())) {
+            matched.push("sha".to_string());
+        }
+        if options.diff != "none"
+            && commit_diff_matches(repo, &commit, &query, pattern.as_ref(), options.case_sensitive)?
+        {
+            matched.push("diff".to_string());
+        }
+        if matched.is_empty() {
+            continue;
+        }
 
-        commits.push(CommitInfo {
-            sha: commit.id().to_string(),
-            message: commit.message().unwrap_or("").to_string(),
-            author: commit.author().name().unwrap_or("Unknown").to_string(),
-            email: commit.author().email().unwrap_or("").to_string(),
-            timestamp: commit.time().seconds(),
-            is_pushed,
-            parents: commit.parent_ids().map(|id| id.to_string()).collect(),
+        let is_pushed = is_reachable_from(repo, upstream_oid, oid);
+        page.push(CommitSearchMatch {
+            commit: commit_info(&commit, is_pushed),
+            matched,
         });
+        summary.total += 1;
+        if page.len() >= page_size {
+            on_page(std::mem::take(&mut page), summary.scanned);
+        }
+        if options.limit > 0 && summary.total >= options.limit {
+            break;
+        }
+    }
+    if !page.is_empty() {
+        on_page(page, summary.scanned);
     }
+    Ok(summary)
+}
 
-    Ok(commits)
+fn commit_info(commit: &git2::Commit, is_pushed: bool) -> CommitInfo {
+    CommitInfo {
+        sha: commit.id().to_string(),
+        message: commit.message().unwrap_or("").to_string(),
+        author: commit.author().name().unwrap_or("Unknown").to_string(),
+        email: commit.author().email().unwrap_or("").to_string(),
+        timestamp: commit.time().seconds(),
+        is_pushed,
+        parents: commit.parent_ids().map(|id| id.to_string()).collect(),
+    }
+}
+
+fn delta_status_name(status: git2::Delta) -> &'static str {
+    match status {
+        git2::Delta::Added => "added",
+        git2::Delta::Deleted => "deleted",
+        git2::Delta::Renamed => "renamed",
+        git2::Delta::Copied => "copied",
+        git2::Delta::Typechange => "typechange",
+        git2::Delta::Untracked => "untracked",
+        git2::Delta::Conflicted => "conflicted",
+        _ => "modified",
+    }
+}
+
+/// Commits listed per side of a comparison; the counts still cover everything.
+const COMPARE_COMMIT_LIMIT: usize = 500;
+
+/// Commits reachable from `tip` but not from `hidden`, newest first.
+fn commits_between(
+    repo: &Repository,
+    tip: git2::Oid,
+    hidden: git2::Oid,
+) -> Result<(Vec<CommitInfo>, usize), String> {
+    let mut revwalk = repo
+        .revwalk()
+        .map_err(|e| format!("Failed to create revwalk: {}", e))?;
+    revwalk.push(tip).map_err(|e| e.to_string())?;
+    revwalk.hide(hidden).map_err(|e| e.to_string())?;
+
+    let mut commits = Vec::new();
+    let mut count = 0;
+    for oid in revwalk {
+        let oid = oid.map_err(|e| format!("Failed to get OID: {}", e))?;
+        count += 1;
+        if commits.len() < COMPARE_COMMIT_LIMIT {
+            let commit = repo
+                .find_commit(oid)
+                .map_err(|e| format!("Failed to find commit: {}", e))?;
+            commits.push(commit_info(&commit, false));
+        }
+    }
+    Ok((commits, count))
+}
+
+/// Compares two revisions. `from` may also be a whole range ("A..B" or "A...B") with `to` empty.
+/// With `merge_base` (or "A...B") the diff starts where the two sides diverged, which is what a
+/// pull request from `to` into `from` would contain.
+pub fn compare_revisions(
+    repo: &Repository,
+    from: &str,
+    to: &str,
+    merge_base: bool,
+    options: &DiffViewOptions,
+) -> Result<RevisionComparison, String> {
+    fn resolve<'r>(object: &git2::Object<'r>, spec: &str) -> Result<git2::Commit<'r>, String> {
+        object
+            .peel_to_commit()
+            .map_err(|e| format!("Unknown revision '{}': {}", spec, e))
+    }
+    let (from_commit, to_commit, merge_base) = if to.trim().is_empty() {
+        let range = repo
+            .revparse(from)
+            .map_err(|e| format!("Invalid revision range '{}': {}", from, e))?;
+        let (from_obj, to_obj) = match (range.from(), range.to()) {
+            (Some(f), Some(t)) => (f, t),
+            _ => return Err(format!("'{}' is not a revision range", from)),
+        };
+        let uses_merge_base = range.mode().contains(git2::RevparseMode::MERGE_BASE);
+        (resolve(from_obj, from)?, resolve(to_obj, from)?, merge_base || uses_merge_base)
+    } else {
+        let find = |spec: &str| {
+            repo.revparse_single(spec)
+                .map_err(|e| format!("Unknown revision '{}': {}", spec, e))
+                .and_then(|o| resolve(&o, spec))
+        };
+        (find(from)?, find(to)?, merge_base)
+    };
+
+    let base_oid = if merge_base {
+        Some(
+            repo.merge_base(from_commit.id(), to_commit.id())
+                .map_err(|e| format!("No common ancestor: {}", e))?,
+        )
+    } else {
+        None
+    };
+    let base_tree = match base_oid {
+        Some(oid) => repo.find_commit(oid).and_then(|c| c.tree()),
+        None => from_commit.tree(),
+    }
+    .map_err(|e| format!("Failed to get tree: {}", e))?;
+    let to_tree = to_commit
+        .tree()
+        .map_err(|e| format!("Failed to get tree: {}", e))?;
+
+    let mut diff_opts = diff_options(options)?;
+    let mut diff = repo
+        .diff_tree_to_tree(Some(&base_tree), Some(&to_tree), Some(&mut diff_opts))
+        .map_err(|e| format!("Failed to generate diff: {}", e))?;
+    find_similar(&mut diff, options)?;
+
+    let (only_in_from, only_in_from_count) = commits_between(repo, from_commit.id(), to_commit.id())?;
+    let (only_in_to, only_in_to_count) = commits_between(repo, to_commit.id(), from_commit.id())?;
+    let (diffs, files) = diff_infos_and_summaries(repo, &diff, options)?;
+
+    Ok(RevisionComparison {
+        from_sha: from_commit.id().to_string(),
+        to_sha: to_commit.id().to_string(),
+        merge_base: base_oid.map(|oid| oid.to_string()),
+        files,
+        diffs,
+        only_in_from,
+        only_in_to,
+        only_in_from_count,
+        only_in_to_count,
+    })
+}
+
+pub fn get_diff(
+    repo: &Repository,
+    path: Option<&str>,
+    options: &DiffViewOptions,
+) -> Result<Vec<DiffInfo>, String> {
+    let diff = workdir_diff(repo, path, options)?;
+    diff_to_infos(repo, &diff, options)
 }
 
-pub fn get_diff(repo: &Repository, path: Option<&str>) -> Result<Vec<DiffInfo>, String> {
+fn workdir_diff<'r>(
+    repo: &'r Repository,
+    path: Option<&str>,
+    options: &DiffViewOptions,
+) -> Result<git2::Diff<'r>, String> {
     let head_tree = repo.head().ok().and_then(|h| h.peel_to_tree().ok());
 
-    let mut opts = DiffOptions::new();
+    let mut opts = diff_options(options)?;
     if let Some(p) = path {
         opts.pathspec(p);
     }
 
-    let diff = if let Some(tree) = head_tree {
+    let mut diff = if let Some(tree) = head_tree {
         repo.diff_tree_to_workdir_with_index(Some(&tree), Some(&mut opts))
             .map_err(|e| format!("Failed to get diff (tree to workdir): {}", e))?
     } else {
         repo.diff_index_to_workdir(None, Some(&mut opts))
             .map_err(|e| format!("Failed to get diff (index to workdir): {}", e))?
     };
-
-    let mut diff_infos = Vec::new();
-
-    diff.print(git2::DiffFormat::Patch, |delta, _hunk, line| {
-        let file_path = delta
-            .new_file()
-            .path()
-            .and_then(|p| p.to_str())
-            .unwrap_or("unknown")
-            .to_string();
-
-        let line_content = String::from_utf8_lossy(line.content()).to_string();
-        let prefix = match line.origin() {
-            '+' => "+",
-            '-' => "-",
-            ' ' => " ",
-            _ => "",
-        };
-
-        if let Some(info) = diff_infos.iter_mut().find(|i: &&mut DiffInfo| i.path == file_path) {
-            info.diff_text.push_str(&format!("{}{}", prefix, line_content));
-            match line.origin() {
-                '+' => info.additions += 1,
-                '-' => info.deletions += 1,
-                _ => {}
-            }
-        } else {
-            diff_infos.push(DiffInfo {
-                path: file_path,
-                diff_text: format!("{}{}", prefix, line_content),
-                additions: if line.origin() == '+' { 1 } else { 0 },
-                deletions: if line.origin() == '-' { 1 } else { 0 },
-            });
-        }
-        true
-    })
-    .map_err(|e| format!("Failed to parse diff: {}", e))?;
-
-    Ok(diff_infos)
+    find_similar(&mut diff, options)?;
+    Ok(diff)
 }
 
 pub fn push_changes(
@@ -789,14 +3083,13 @@ pub fn pull_changes(
     Ok(())
 }
 
-pub fn stash_save(repo: &mut Repository, message: Option<&str>) -> Result<(), String> {
-    let signature = repo
-        .signature()
-        .or_else(|_| Signature::now("User", "user@example.com"))
-        .map_err(|e| format!("Failed to create signature: {}", e))?;
-
+pub fn stash_save(
+    repo: &mut Repository,
+    message: Option<&str>,
+    signature: &Signature,
+) -> Result<(), String> {
     repo.stash_save(
-        &signature,
+        signature,
         message.unwrap_or(""),
         Some(StashFlags::INCLUDE_UNTRACKED),
     )
@@ -925,26 +3218,1400 @@ pub fn set_remote_url(repo: &Repository, name: &str, url: &str) -> Result<(), St
     Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
-    use std::path::PathBuf;
+fn ssh_command_env(ssh_key_path: Option<&str>) -> Vec<(&'static str, String)> {
+    let mut envs = Vec::new();
+    if let Some(key) = ssh_key_path {
+        if !key.trim().is_empty() {
+            let escaped_path = expand_home(key).replace('"', "\\\"");
+            envs.push((
+                "GIT_SSH_COMMAND",
+                format!("ssh -i \"{}\" -o IdentitiesOnly=yes", escaped_path),
+            ));
+        }
+    }
+    envs
+}
 
-    fn get_temp_dir() -> PathBuf {
-        let mut path = std::env::temp_dir();
-        path.push("tauri_git_test");
-        path.push(format!("{}", std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos()));
-        fs::create_dir_all(&path).unwrap();
-        path
+pub fn list_submodules(repo: &Repository) -> Result<Vec<SubmoduleInfo>, String> {
+    let submodules = repo
+        .submodules()
+        .map_err(|e| format!("Failed to list submodules: {}", e))?;
+
+    let mut infos = Vec::new();
+    for sub in submodules {
+        let name = sub.name().unwrap_or("").to_string();
+        let status = repo
+            .submodule_status(&name, git2::SubmoduleIgnore::None)
+            .unwrap_or(git2::SubmoduleStatus::empty());
+        let dirty = status.intersects(
+            git2::SubmoduleStatus::WD_INDEX_MODIFIED
+                | git2::SubmoduleStatus::WD_WD_MODIFIED
+                | git2::SubmoduleStatus::WD_UNTRACKED,
+        );
+        let recorded = sub.index_id().or_else(|| sub.head_id());
+        let checked_out = sub.workdir_id();
+
+        infos.push(SubmoduleInfo {
+            name,
+            path: sub.path().to_string_lossy().to_string(),
+            url: sub.url().map(|u| u.to_string()),
+            branch: sub.branch().map(|b| b.to_string()),
+            recorded_commit: recorded.map(|id| id.to_string()),
+            checked_out_commit: checked_out.map(|id| id.to_string()),
+            initialized: !status.contains(git2::SubmoduleStatus::WD_UNINITIALIZED),
+            dirty,
+            out_of_sync: checked_out.is_some() && checked_out != recorded,
+        });
     }
 
-    #[test]
-    fn test_pull_changes() {
-        // Setup origin
-        let root = get_temp_dir();
-        let origin_path = root.join("origin");
-        let local_path = root.join("local");
+    Ok(infos)
+}
+
+/// Maps requested paths onto known submodule paths, rejecting anything else.
+fn known_submodule_paths(repo: &Repository, paths: &[String]) -> Result<Vec<String>, String> {
+    let known: Vec<String> = repo
+        .submodules()
+        .map_err(|e| format!("Failed to list submodules: {}", e))?
+        .iter()
+        .map(|s| s.path().to_string_lossy().to_string())
+        .collect();
+
+    for path in paths {
+        if !known.contains(path) {
+            return Err(format!("'{}' is not a submodule", path));
+        }
+    }
+    Ok(paths.to_vec())
+}
+
+/// Copies submodule URLs from `.gitmodules` into `.git/config` (`git submodule init`).
+pub fn init_submodules(repo: &Repository, paths: &[String]) -> Result<(), String> {
+    let paths = known_submodule_paths(repo, paths)?;
+    let submodules = repo
+        .submodules()
+        .map_err(|e| format!("Failed to list submodules: {}", e))?;
+    for mut sub in submodules {
+        let path = sub.path().to_string_lossy().to_string();
+        if paths.is_empty() || paths.contains(&path) {
+            sub.init(false)
+                .map_err(|e| format!("Failed to init submodule '{}': {}", path, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks out the recorded commit of each submodule, initializing and cloning as needed.
+pub fn update_submodules(
+    repo: &Repository,
+    paths: &[String],
+    recursive: bool,
+    ssh_key_path: Option<&str>,
+) -> Result<(), String> {
+    let paths = known_submodule_paths(repo, paths)?;
+    let workdir = repo
+        .workdir()
+        .ok_or("No working directory found")?
+        .to_str()
+        .ok_or("Invalid path")?;
+
+    let mut args = vec!["submodule", "update", "--init"];
+    if recursive {
+        args.push("--recursive");
+    }
+    args.push("--");
+    args.extend(paths.iter().map(|p| p.as_str()));
+    run_git_command(args, Some(workdir), ssh_command_env(ssh_key_path))?;
+    Ok(())
+}
+
+/// Re-syncs submodule remote URLs after `.gitmodules` changed (`git submodule sync`).
+pub fn sync_submodules(repo: &Repository, recursive: bool) -> Result<(), String> {
+    let workdir = repo
+        .workdir()
+        .ok_or("No working directory found")?
+        .to_str()
+        .ok_or("Invalid path")?;
+
+    let mut args = vec!["submodule", "sync"];
+    if recursive {
+        args.push("--recursive");
+    }
+    run_git_command(args, Some(workdir), vec![])?;
+    Ok(())
+}
+
+/// Returns the absolute working-tree path of a submodule so it can be opened as a repository.
+pub fn get_submodule_path(repo: &Repository, path: &str) -> Result<String, String> {
+    known_submodule_paths(repo, &[path.to_string()])?;
+    let full_path = repo.workdir().ok_or("No working directory found")?.join(path);
+    if !full_path.join(".git").exists() {
+        return Err(format!("Submodule '{}' is not initialized", path));
+    }
+    full_path
+        .to_str()
+        .map(|p| p.to_string())
+        .ok_or_else(|| "Invalid path".to_string())
+}
+
+fn worktree_head(repo: &Repository) -> (Option<String>, Option<String>) {
+    match repo.head() {
+        Ok(head) => {
+            let branch = if head.is_branch() {
+                head.shorthand().map(|s| s.to_string())
+            } else {
+                None
+            };
+            (branch, head.target().map(|oid| oid.to_string()))
+        }
+        Err(_) => (None, None),
+    }
+}
+
+/// Lists the main worktree followed by all linked worktrees, whichever of them `repo` is.
+pub fn list_worktrees(repo: &Repository) -> Result<Vec<WorktreeInfo>, String> {
+    let main = Repository::open(common_git_dir(repo))
+        .map_err(|e| format!("Failed to open main repository: {}", e))?;
+    let current = repo.workdir().map(normalize_path);
+
+    let mut worktrees = Vec::new();
+    if let Some(main_path) = main.workdir().map(normalize_path) {
+        let (branch, head) = worktree_head(&main);
+        worktrees.push(WorktreeInfo {
+            name: None,
+            is_current: current.as_deref() == Some(main_path.as_str()),
+            path: main_path,
+            branch,
+            head,
+            is_main: true,
+            is_locked: false,
+            lock_reason: None,
+            is_prunable: false,
+        });
+    }
+
+    let names = main
+        .worktrees()
+        .map_err(|e| format!("Failed to list worktrees: {}", e))?;
+    for name in names.iter().flatten() {
+        let worktree = main
+            .find_worktree(name)
+            .map_err(|e| format!("Failed to read worktree '{}': {}", name, e))?;
+        let path = normalize_path(worktree.path());
+        let (is_locked, lock_reason) = match worktree.is_locked() {
+            Ok(git2::WorktreeLockStatus::Locked(reason)) => (true, reason),
+            _ => (false, None),
+        };
+        let (branch, head) = Repository::open_from_worktree(&worktree)
+            .map(|wt_repo| worktree_head(&wt_repo))
+            .unwrap_or((None, None));
+
+        worktrees.push(WorktreeInfo {
+            name: Some(name.to_string()),
+            is_current: current.as_deref() == Some(path.as_str()),
+            path,
+            branch,
+            head,
+            is_main: false,
+            is_locked,
+            lock_reason,
+            is_prunable: worktree.validate().is_err(),
+        });
+    }
+
+    Ok(worktrees)
+}
+
+fn main_workdir(repo: &Repository) -> Result<String, String> {
+    let main = Repository::open(common_git_dir(repo))
+        .map_err(|e| format!("Failed to open main repository: {}", e))?;
+    main.workdir()
+        .map(normalize_path)
+        .ok_or_else(|| "No working directory found".to_string())
+}
+
+/// Adds a linked worktree for an existing branch, or for a new branch started at `base`.
+pub fn add_worktree(
+    repo: &Repository,
+    path: &str,
+    branch: &str,
+    create_branch: bool,
+    base: Option<&str>,
+) -> Result<(), String> {
+    if !is_safe_git_arg(branch) {
+        return Err("Invalid branch name".to_string());
+    }
+    if path.trim().is_empty() || path.starts_with('-') {
+        return Err("Invalid worktree path".to_string());
+    }
+    if let Some(b) = base {
+        if !is_safe_git_arg(b) {
+            return Err("Invalid base revision".to_string());
+        }
+    }
+
+    let cwd = main_workdir(repo)?;
+    let mut args = vec!["worktree", "add"];
+    if create_branch {
+        args.extend(["-b", branch, "--", path]);
+        if let Some(b) = base {
+            args.push(b);
+        }
+    } else {
+        args.extend(["--", path, branch]);
+    }
+    run_git_command(args, Some(&cwd), vec![])?;
+    Ok(())
+}
+
+/// Removes a linked worktree. Without `force`, git refuses when it has local changes.
+pub fn remove_worktree(repo: &Repository, path: &str, force: bool) -> Result<(), String> {
+    let worktrees = list_worktrees(repo)?;
+    let target = worktrees
+        .iter()
+        .find(|w| w.path == normalize_path(Path::new(path)))
+        .ok_or_else(|| format!("'{}' is not a worktree of this repository", path))?;
+    if target.is_main {
+        return Err("The main worktree cannot be removed".to_string());
+    }
+    if target.is_current {
+        return Err("Cannot remove the worktree that is currently open".to_string());
+    }
+
+    let cwd = main_workdir(repo)?;
+    let mut args = vec!["worktree", "remove"];
+    if force {
+        args.push("--force");
+    }
+    args.extend(["--", target.path.as_str()]);
+    run_git_command(args, Some(&cwd), vec![])?;
+    Ok(())
+}
+
+/// Cleans up administrative data for worktrees whose directories were deleted.
+pub fn prune_worktrees(repo: &Repository) -> Result<(), String> {
+    let cwd = main_workdir(repo)?;
+    run_git_command(vec!["worktree", "prune"], Some(&cwd), vec![])?;
+    Ok(())
+}
+
+const LFS_POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/v1";
+/// Pointers are tiny text files; anything larger is real content.
+const LFS_POINTER_MAX_SIZE: u64 = 1024;
+
+/// Parses a Git LFS pointer file into (oid, size).
+fn parse_lfs_pointer(content: &[u8]) -> Option<(String, u64)> {
+    if content.len() as u64 > LFS_POINTER_MAX_SIZE {
+        return None;
+    }
+    let text = std::str::from_utf8(content).ok()?;
+    let mut lines = text.lines();
+    if lines.next()? != LFS_POINTER_VERSION {
+        return None;
+    }
+
+    let mut oid = None;
+    let mut size = None;
+    for line in lines {
+        if let Some(value) = line.strip_prefix("oid sha256:") {
+            oid = Some(value.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("size ") {
+            size = value.trim().parse::<u64>().ok();
+        }
+    }
+    Some((oid?, size?))
+}
+
+/// Where git-lfs keeps a downloaded object, None for malformed oids.
+fn lfs_object_path(repo: &Repository, oid: &str) -> Option<PathBuf> {
+    if oid.len() < 5 {
+        return None;
+    }
+    let objects = common_git_dir(repo).join("lfs").join("objects");
+    Some(objects.join(&oid[0..2]).join(&oid[2..4]).join(oid))
+}
+
+fn lfs_object_present(repo: &Repository, oid: &str) -> bool {
+    lfs_object_path(repo, oid).map(|p| p.is_file()).unwrap_or(false)
+}
+
+fn is_lfs_tracked(repo: &Repository, path: &Path) -> bool {
+    matches!(
+        repo.get_attr(path, "filter", git2::AttrCheckFlags::FILE_THEN_INDEX),
+        Ok(Some("lfs"))
+    )
+}
+
+/// Content of one side of a diff: the blob when its id is known, otherwise the working-tree file.
+fn diff_file_content(repo: &Repository, file: &git2::DiffFile) -> Option<Vec<u8>> {
+    let path = file.path()?;
+    let content = if !file.id().is_zero() {
+        repo.find_blob(file.id()).ok().map(|b| b.content().to_vec())
+    } else {
+        None
+    };
+    match content {
+        Some(c) => Some(c),
+        None => std::fs::read(repo.workdir()?.join(path)).ok(),
+    }
+}
+
+/// Size of one side of a diff without reading it: the object header when the blob is stored,
+/// otherwise the working-tree file's metadata.
+fn diff_file_len(repo: &Repository, file: &git2::DiffFile) -> Option<u64> {
+    let path = file.path()?;
+    if !file.id().is_zero() {
+        let header = repo.odb().ok().and_then(|odb| odb.read_header(file.id()).ok());
+        if let Some((size, _)) = header {
+            return Some(size as u64);
+        }
+    }
+    std::fs::metadata(repo.workdir()?.join(path)).ok().map(|m| m.len())
+}
+
+/// Describes one side of a diff as an LFS object. Only sides small enough to be a pointer
+/// are read; larger ones are sized from the object header or file metadata.
+fn lfs_side(repo: &Repository, file: &git2::DiffFile, lfs_tracked: bool) -> Option<LfsObject> {
+    if !file.exists() {
+        return None;
+    }
+    let size = diff_file_len(repo, file)?;
+    if size <= LFS_POINTER_MAX_SIZE {
+        let content = diff_file_content(repo, file)?;
+        if let Some((oid, size)) = parse_lfs_pointer(&content) {
+            let present = lfs_object_present(repo, &oid);
+            return Some(LfsObject { oid: Some(oid), size, present });
+        }
+    }
+    // A smudged working-tree file of an LFS path: it is the object itself
+    if lfs_tracked {
+        return Some(LfsObject { oid: None, size, present: true });
+    }
+    None
+}
+
+/// Translates the UI's diff settings into libgit2 options.
+fn diff_options(options: &DiffViewOptions) -> Result<DiffOptions, String> {
+    let mut opts = DiffOptions::new();
+    match options.ignore_whitespace.as_str() {
+        "none" | "" => {}
+        "all" => {
+            opts.ignore_whitespace(true);
+        }
+        "change" => {
+            opts.ignore_whitespace_change(true);
+        }
+        "eol" => {
+            opts.ignore_whitespace_eol(true);
+        }
+        other => return Err(format!("Unknown whitespace mode: {}", other)),
+    }
+    match options.algorithm.as_str() {
+        "myers" | "" => {}
+        "minimal" => {
+            opts.minimal(true);
+        }
+        "patience" => {
+            opts.patience(true);
+        }
+        // libgit2 has no histogram implementation; rather than silently diffing with another
+        // algorithm it is rejected like any unknown name
+        "histogram" => return Err("The histogram diff algorithm is not supported".to_string()),
+        other => return Err(format!("Unknown diff algorithm: {}", other)),
+    }
+    if options.max_file_size > 0 {
+        // libgit2 then treats larger blobs as binary instead of loading them for a text diff
+        opts.max_size(options.max_file_size as i64);
+    }
+    opts.ignore_blank_lines(options.ignore_blank_lines)
+        .context_lines(options.context_lines)
+        .interhunk_lines(options.interhunk_lines)
+        .indent_heuristic(true);
+    Ok(opts)
+}
+
+/// Pairs deleted and added files into renames and copies when enabled.
+fn find_similar(diff: &mut git2::Diff, options: &DiffViewOptions) -> Result<(), String> {
+    if !options.detect_renames && !options.detect_copies {
+        return Ok(());
+    }
+    let mut find_opts = git2::DiffFindOptions::new();
+    find_opts
+        .renames(options.detect_renames)
+        .copies(options.detect_copies)
+        .rename_threshold(options.rename_threshold)
+        .copy_threshold(options.copy_threshold);
+    diff.find_similar(Some(&mut find_opts))
+        .map_err(|e| format!("Failed to detect renames: {}", e))
+}
+
+/// Splits a line into diff tokens: single characters, or for "word" runs of word characters,
+/// runs of whitespace and single punctuation characters.
+fn intraline_tokens<'a>(line: &'a str, granularity: &str) -> Vec<&'a str> {
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    };
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut prev: Option<u8> = None;
+    for (idx, c) in line.char_indices() {
+        let current = class(c);
+        let joins = granularity == "word" && prev == Some(current) && current != 2;
+        if idx > 0 && !joins {
+            tokens.push(&line[start..idx]);
+            start = idx;
+        }
+        prev = Some(current);
+    }
+    if start < line.len() {
+        tokens.push(&line[start..]);
+    }
+    tokens
+}
+
+/// Beyond this many token comparisons the changed middle of a line is highlighted as a whole.
+const INTRALINE_MAX_CELLS: usize = 250_000;
+
+/// Returns the changed ranges of `old` and `new` as UTF-16 offsets.
+fn intraline_ranges(old: &str, new: &str, granularity: &str) -> (Vec<TextRange>, Vec<TextRange>) {
+    let old_tokens = intraline_tokens(old, granularity);
+    let new_tokens = intraline_tokens(new, granularity);
+
+    let prefix = old_tokens
+        .iter()
+        .zip(&new_tokens)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old_tokens[prefix..]
+        .iter()
+        .rev()
+        .zip(new_tokens[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old_tokens[prefix..old_tokens.len() - suffix];
+    let new_mid = &new_tokens[prefix..new_tokens.len() - suffix];
+
+    // Which middle tokens are unchanged, via an LCS table
+    let mut old_kept = vec![false; old_mid.len()];
+    let mut new_kept = vec![false; new_mid.len()];
+    if !old_mid.is_empty() && !new_mid.is_empty() && old_mid.len() * new_mid.len() <= INTRALINE_MAX_CELLS {
+        let width = new_mid.len() + 1;
+        let mut table = vec![0u32; (old_mid.len() + 1) * width];
+        for i in (0..old_mid.len()).rev() {
+            for j in (0..new_mid.len()).rev() {
+                table[i * width + j] = if old_mid[i] == new_mid[j] {
+                    table[(i + 1) * width + j + 1] + 1
+                } else {
+                    table[(i + 1) * width + j].max(table[i * width + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < old_mid.len() && j < new_mid.len() {
+            if old_mid[i] == new_mid[j] {
+                old_kept[i] = true;
+                new_kept[j] = true;
+                i += 1;
+                j += 1;
+            } else if table[(i + 1) * width + j] >= table[i * width + j + 1] {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+    }
+
+    let to_ranges = |tokens: &[&str], kept: &[bool]| {
+        let mut ranges: Vec<TextRange> = Vec::new();
+        let mut offset: usize = tokens[..prefix].iter().map(|t| t.encode_utf16().count()).sum();
+        for (token, kept) in tokens[prefix..tokens.len() - suffix].iter().zip(kept) {
+            let len = token.encode_utf16().count();
+            if !kept {
+                match ranges.last_mut() {
+                    Some(last) if last.end == offset => last.end += len,
+                    _ => ranges.push(TextRange {
+                        start: offset,
+                        end: offset + len,
+                    }),
+                }
+            }
+            offset += len;
+        }
+        ranges
+    };
+    (to_ranges(&old_tokens, &old_kept), to_ranges(&new_tokens, &new_kept))
+}
+
+/// Pairs each run of removed lines with the added lines that directly follow it and records
+/// which parts of each pair changed. `granularity` is "word", "char" or "none".
+fn patch_highlights(patch: &git2::Patch, granularity: &str) -> Vec<LineHighlight> {
+    let mut highlights = Vec::new();
+    if granularity != "word" && granularity != "char" {
+        return highlights;
+    }
+    for hunk_idx in 0..patch.num_hunks() {
+        let line_count = patch.num_lines_in_hunk(hunk_idx).unwrap_or(0);
+        let lines: Vec<git2::DiffLine> = (0..line_count)
+            .filter_map(|i| patch.line_in_hunk(hunk_idx, i).ok())
+            .collect();
+        let mut idx = 0;
+        while idx < lines.len() {
+            if lines[idx].origin() != '-' {
+                idx += 1;
+                continue;
+            }
+            let removed_start = idx;
+            while idx < lines.len() && lines[idx].origin() == '-' {
+                idx += 1;
+            }
+            let added_start = idx;
+            while idx < lines.len() && lines[idx].origin() == '+' {
+                idx += 1;
+            }
+            let pairs = (added_start - removed_start).min(idx - added_start);
+            for k in 0..pairs {
+                let old_line = &lines[removed_start + k];
+                let new_line = &lines[added_start + k];
+                let old_text = String::from_utf8_lossy(old_line.content());
+                let new_text = String::from_utf8_lossy(new_line.content());
+                let (old_ranges, new_ranges) = intraline_ranges(
+                    old_text.trim_end_matches(['\n', '\r']),
+                    new_text.trim_end_matches(['\n', '\r']),
+                    granularity,
+                );
+                highlights.push(LineHighlight {
+                    old_lineno: old_line.old_lineno(),
+                    new_lineno: None,
+                    ranges: old_ranges,
+                });
+                highlights.push(LineHighlight {
+                    old_lineno: None,
+                    new_lineno: new_line.new_lineno(),
+                    ranges: new_ranges,
+                });
+            }
+        }
+    }
+    highlights
+}
+
+/// LFS object metadata for a delta whose old or new side is an LFS pointer; its text diff
+/// would only show pointer lines.
+fn delta_lfs_info(repo: &Repository, delta: &git2::DiffDelta) -> Option<LfsDiffInfo> {
+    let path = delta.new_file().path().or_else(|| delta.old_file().path())?;
+    let tracked = is_lfs_tracked(repo, path);
+    let old = lfs_side(repo, &delta.old_file(), tracked);
+    let new = lfs_side(repo, &delta.new_file(), tracked);
+    // Only treat the file as LFS when at least one side is an actual pointer
+    let has_pointer = [&old, &new]
+        .iter()
+        .any(|side| side.as_ref().map(|o| o.oid.is_some()).unwrap_or(false));
+    if has_pointer {
+        Some(LfsDiffInfo { old, new })
+    } else {
+        None
+    }
+}
+
+/// Runs git with stdout written straight to `output`, so patches keep their exact bytes.
+fn run_git_to_file(args: &[&str], cwd: &Path, output: &mut std::fs::File) -> Result<(), String> {
+    let result = Command::new("git")
+        .args(args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .current_dir(cwd)
+        .stdout(output.try_clone().map_err(|e| format!("Failed to open patch file: {}", e))?)
+        .output()
+        .map_err(|e| format!("Failed to execute git command: {}", e))?;
+    if result.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&result.stderr).trim().to_string())
+    }
+}
+
+/// Diff options that keep exported patches applicable whatever the user's diff config says
+/// (color.ui=always, diff.noprefix, textconv drivers or an external diff tool).
+const PLAIN_PATCH_ARGS: [&str; 6] = [
+    "--binary",
+    "--no-color",
+    "--no-ext-diff",
+    "--no-textconv",
+    "--src-prefix=a/",
+    "--dst-prefix=b/",
+];
+
+/// Writes commits as a `git format-patch` mbox, one message per commit, ordered oldest first
+/// (topologically) whatever order they were selected in, so `git am` can replay the series.
+/// Returns the number of patches.
+pub fn export_commit_patches(
+    repo: &Repository,
+    shas: &[String],
+    output_path: &str,
+) -> Result<usize, String> {
+    let workdir = repo.workdir().ok_or("No working directory found")?;
+    if shas.is_empty() {
+        return Err("No commits selected".to_string());
+    }
+    let mut selected = std::collections::HashSet::new();
+    for sha in shas {
+        let commit = repo
+            .revparse_single(sha)
+            .and_then(|o| o.peel_to_commit())
+            .map_err(|e| format!("Failed to find commit {}: {}", sha, e))?;
+        selected.insert(commit.id());
+    }
+
+    // Walk newest first from the selection and stop once every selected commit has been seen
+    let mut revwalk = repo.revwalk().map_err(|e| format!("Failed to create revwalk: {}", e))?;
+    revwalk
+        .set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)
+        .map_err(|e| format!("Failed to set sorting: {}", e))?;
+    for oid in &selected {
+        revwalk.push(*oid).map_err(|e| format!("Failed to push commit: {}", e))?;
+    }
+    let mut commits = Vec::new();
+    for oid in revwalk {
+        let oid = oid.map_err(|e| format!("Failed to walk history: {}", e))?;
+        if selected.contains(&oid) {
+            commits.push(oid.to_string());
+            if commits.len() == selected.len() {
+                break;
+            }
+        }
+    }
+    commits.reverse();
+
+    let mut file = std::fs::File::create(output_path)
+        .map_err(|e| format!("Failed to create patch file: {}", e))?;
+    for sha in &commits {
+        let mut args = vec!["format-patch", "-1", "--stdout"];
+        args.extend(PLAIN_PATCH_ARGS);
+        args.push(sha);
+        run_git_to_file(&args, workdir, &mut file)
+            .map_err(|e| format!("Failed to format patch for {}: {}", sha, e))?;
+    }
+    Ok(commits.len())
+}
+
+/// Writes a diff as a `.patch` file that `git apply` accepts, binary changes included.
+/// Untracked files are part of a "workdir" or "unstaged" patch only once they are staged.
+pub fn export_diff_patch(
+    repo: &Repository,
+    source: &DiffSource,
+    output_path: &str,
+) -> Result<(), String> {
+    let workdir = repo.workdir().ok_or("No working directory found")?;
+    let commit_range;
+    let mut args: Vec<&str> = match source.kind.as_str() {
+        "workdir" if repo.head().is_ok() => vec!["diff", "HEAD"],
+        "workdir" | "staged" => vec!["diff", "--cached"],
+        "unstaged" => vec!["diff"],
+        "commit" => {
+            let sha = source.sha.as_deref().ok_or("A commit diff needs a sha")?;
+            let commit = repo
+                .revparse_single(sha)
+                .and_then(|o| o.peel_to_commit())
+                .map_err(|e| format!("Failed to find commit: {}", e))?;
+            commit_range = match commit.parent_count() {
+                0 => vec![commit.id().to_string()],
+                count if source.parent < count => {
+                    let parent = commit.parent_id(source.parent).map_err(|e| e.to_string())?;
+                    vec![parent.to_string(), commit.id().to_string()]
+                }
+                count => {
+                    return Err(format!(
+                        "Commit has {} parent(s); parent {} does not exist",
+                        count,
+                        source.parent + 1
+                    ))
+                }
+            };
+            let mut args = vec!["diff-tree", "-p", "--root"];
+            args.extend(commit_range.iter().map(String::as_str));
+            args
+        }
+        other => return Err(format!("Unknown diff source: {}", other)),
+    };
+    // Options go before the revisions so they aren't taken for paths
+    args.splice(1..1, PLAIN_PATCH_ARGS);
+    let mut file = std::fs::File::create(output_path)
+        .map_err(|e| format!("Failed to create patch file: {}", e))?;
+    run_git_to_file(&args, workdir, &mut file).map_err(|e| format!("Failed to export diff: {}", e))
+}
+
+/// Hunk header of the patch whose old side starts at `line` in `path`, for reporting failures.
+fn find_patch_hunk(patch: &str, path: &str, line: u32) -> Option<String> {
+    let mut current = None;
+    for text in patch.lines() {
+        if let Some(name) = text.strip_prefix("+++ ") {
+            current = Some(name.strip_prefix("b/").unwrap_or(name));
+        } else if let Some(name) = text.strip_prefix("--- ") {
+            if name != "/dev/null" {
+                current = Some(name.strip_prefix("a/").unwrap_or(name));
+            }
+        } else if text.starts_with("@@ -") && current == Some(path) {
+            let start = text[4..].split([',', ' ']).next()?.parse::<u32>().ok()?;
+            if start == line {
+                return Some(text.to_string());
+            }
+        }
+    }
+    None
+}
+
+/// Reads `git apply --check -v` output into per-file failures.
+fn parse_apply_failures(output: &str, patch: &str) -> Vec<PatchFailure> {
+    let mut failures: Vec<PatchFailure> = Vec::new();
+    let mut searched: Option<String> = None;
+    let mut lines = output.lines().peekable();
+    while let Some(line) = lines.next() {
+        let Some(message) = line.strip_prefix("error: ") else {
+            continue;
+        };
+        if message.starts_with("while searching for:") {
+            // The expected context follows until the next "error:" line
+            let mut context = Vec::new();
+            while let Some(next) = lines.peek() {
+                if next.starts_with("error: ") {
+                    break;
+                }
+                context.push(*next);
+                lines.next();
+            }
+            searched = Some(context.join("\n").trim_end().to_string());
+        } else if let Some(location) = message.strip_prefix("patch failed: ") {
+            let (path, line) = match location.rsplit_once(':') {
+                Some((path, line)) => (path, line.parse::<u32>().ok()),
+                None => (location, None),
+            };
+            failures.push(PatchFailure {
+                path: path.to_string(),
+                line,
+                hunk: line.and_then(|l| find_patch_hunk(patch, path, l)),
+                expected: searched.take(),
+                message: message.to_string(),
+            });
+        } else if let Some((path, reason)) = message.split_once(": ") {
+            // "<path>: patch does not apply" follows the hunk failures of the same file
+            if failures.iter().any(|f| f.path == path) {
+                continue;
+            }
+            failures.push(PatchFailure {
+                path: path.to_string(),
+                line: None,
+                hunk: None,
+                expected: None,
+                message: reason.to_string(),
+            });
+        } else {
+            failures.push(PatchFailure {
+                path: String::new(),
+                line: None,
+                hunk: None,
+                expected: None,
+                message: message.to_string(),
+            });
+        }
+    }
+    failures
+}
+
+/// Applies a patch or mbox to the working tree ("worktree"), the index only ("index"), or as
+/// commits with their original authorship ("am"). The patch is always checked first; when any
+/// hunk would fail nothing is changed and the failures are reported. `check_only` stops there.
+/// `committer` is only needed when commits are actually created ("am" without `check_only`).
+pub fn apply_patch(
+    repo: &Repository,
+    options: &ApplyPatchOptions,
+    committer: Option<&CommitIdentity>,
+) -> Result<PatchApplyResult, String> {
+    let workdir = repo.workdir().ok_or("No working directory found")?;
+    let cwd = workdir.to_str();
+    let patch = std::fs::read(&options.path).map_err(|e| format!("Failed to read patch: {}", e))?;
+    let patch = String::from_utf8_lossy(&patch);
+
+    let target_flag = match options.target.as_str() {
+        "worktree" => None,
+        "index" => Some("--cached"),
+        "am" => Some("--index"),
+        other => return Err(format!("Unknown patch target: {}", other)),
+    };
+    // "--" keeps a path starting with '-' from being read as an option
+    let args = vec!["apply", "--numstat", "--", &options.path];
+    let numstat = run_git_command(args, cwd, vec![])
+        .map_err(|e| format!("Failed to read patch: {}", e))?;
+    let files = numstat
+        .lines()
+        .filter_map(|l| l.splitn(3, '\t').nth(2))
+        .map(String::from)
+        .collect();
+
+    let mut args = vec!["apply", "--check", "-v"];
+    args.extend(target_flag);
+    args.extend(["--", &options.path]);
+    let check = Command::new("git")
+        .args(&args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .current_dir(workdir)
+        .output()
+        .map_err(|e| format!("Failed to execute git command: {}", e))?;
+    let mut result = PatchApplyResult {
+        applied: false,
+        files,
+        failures: Vec::new(),
+    };
+    if !check.status.success() {
+        result.failures = parse_apply_failures(&String::from_utf8_lossy(&check.stderr), &patch);
+        if result.failures.is_empty() {
+            return Err(format!(
+                "Failed to check patch: {}",
+                String::from_utf8_lossy(&check.stderr).trim()
+            ));
+        }
+        return Ok(result);
+    }
+    if options.check_only {
+        return Ok(result);
+    }
+
+    if options.target == "am" {
+        let committer = committer.ok_or("A committer identity is required to apply commits")?;
+        let envs = vec![
+            ("GIT_COMMITTER_NAME", committer.name.clone()),
+            ("GIT_COMMITTER_EMAIL", committer.email.clone()),
+        ];
+        if let Err(e) = run_git_command(vec!["am", "--keep-cr", "--", &options.path], cwd, envs) {
+            // Leave the repository as it was rather than in the middle of an am session
+            let _ = run_git_command(vec!["am", "--abort"], cwd, vec![]);
+            return Err(format!("Failed to apply patch: {}", e));
+        }
+    } else {
+        let mut args = vec!["apply"];
+        args.extend(target_flag);
+        args.extend(["--", &options.path]);
+        run_git_command(args, cwd, vec![]).map_err(|e| format!("Failed to apply patch: {}", e))?;
+    }
+    result.applied = true;
+    Ok(result)
+}
+
+/// Output kept per bisect test run; earlier output is dropped.
+const BISECT_OUTPUT_LIMIT: usize = 64 * 1024;
+
+/// Current bisect session, read from `BISECT_START` and the `refs/bisect/*` refs.
+pub fn bisect_status(repo: &Repository) -> Result<BisectStatus, String> {
+    let mut status = BisectStatus {
+        active: false,
+        original_branch: None,
+        current: None,
+        bad: None,
+        good: Vec::new(),
+        skipped: Vec::new(),
+        remaining: 0,
+        estimated_steps: 0,
+        first_bad: None,
+        candidates: Vec::new(),
+    };
+    let Ok(start) = std::fs::read_to_string(repo.path().join("BISECT_START")) else {
+        return Ok(status);
+    };
+    status.active = true;
+    status.original_branch = Some(start.trim().to_string()).filter(|s| !s.is_empty());
+    status.current = repo.head().ok().and_then(|h| h.target()).map(|oid| oid.to_string());
+
+    let targets = |glob: &str| -> Result<Vec<git2::Oid>, String> {
+        let refs = repo
+            .references_glob(glob)
+            .map_err(|e| format!("Failed to read bisect refs: {}", e))?;
+        Ok(refs.flatten().filter_map(|r| r.target()).collect())
+    };
+    let bad = targets("refs/bisect/bad")?.into_iter().next();
+    let good = targets("refs/bisect/good-*")?;
+    let skipped = targets("refs/bisect/skip-*")?;
+    status.bad = bad.map(|oid| oid.to_string());
+    status.good = good.iter().map(|oid| oid.to_string()).collect();
+    status.skipped = skipped.iter().map(|oid| oid.to_string()).collect();
+
+    // Nothing to narrow down until both ends are known
+    let Some(bad) = bad else {
+        return Ok(status);
+    };
+    if good.is_empty() {
+        return Ok(status);
+    }
+    let mut revwalk = repo
+        .revwalk()
+        .map_err(|e| format!("Failed to create revwalk: {}", e))?;
+    revwalk.push(bad).map_err(|e| format!("Failed to walk bisect range: {}", e))?;
+    for oid in &good {
+        revwalk.hide(*oid).map_err(|e| format!("Failed to walk bisect range: {}", e))?;
+    }
+    let candidates = revwalk
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to walk bisect range: {}", e))?;
+    status.remaining = candidates
+        .iter()
+        .filter(|oid| **oid != bad && !skipped.contains(oid))
+        .count();
+    // Each answer halves the untested range
+    status.estimated_steps = (usize::BITS - status.remaining.leading_zeros()) as usize;
+
+    if status.remaining == 0 {
+        if candidates == [bad] {
+            let commit = repo
+                .find_commit(bad)
+                .map_err(|e| format!("Failed to find commit: {}", e))?;
+            let is_pushed = is_reachable_from(repo, head_upstream_oid(repo), bad);
+            status.first_bad = Some(commit_info(&commit, is_pushed));
+        } else {
+            status.candidates = candidates.iter().map(|oid| oid.to_string()).collect();
+        }
+    }
+    Ok(status)
+}
+
+/// Full id of the commit `spec` names, for passing to `git bisect`.
+fn bisect_commit_id(repo: &Repository, spec: &str) -> Result<String, String> {
+    repo.revparse_single(spec)
+        .and_then(|o| o.peel_to_commit())
+        .map(|c| c.id().to_string())
+        .map_err(|e| format!("Unknown revision '{}': {}", spec, e))
+}
+
+/// Starts bisecting between a bad commit and one or more good ones and checks out the midpoint.
+pub fn bisect_start(repo: &Repository, bad: &str, good: &[String]) -> Result<BisectStatus, String> {
+    let workdir = repo.workdir().ok_or("No working directory found")?;
+    if good.is_empty() {
+        return Err("At least one good commit is required".to_string());
+    }
+    if bisect_status(repo)?.active {
+        return Err("A bisect is already in progress".to_string());
+    }
+    // Resolved ids keep user input from being parsed as bisect options
+    let bad = bisect_commit_id(repo, bad)?;
+    let good = good
+        .iter()
+        .map(|sha| bisect_commit_id(repo, sha))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut args = vec!["bisect", "start", bad.as_str()];
+    args.extend(good.iter().map(String::as_str));
+    args.push("--");
+    run_git_command(args, workdir.to_str(), vec![])
+        .map_err(|e| format!("Failed to start bisect: {}", e))?;
+    bisect_status(repo)
+}
+
+/// Marks a commit (the checked-out one when `sha` is None) as "good", "bad" or "skip";
+/// git then checks out the next commit to test.
+pub fn bisect_mark(
+    repo: &Repository,
+    verdict: &str,
+    sha: Option<&str>,
+) -> Result<BisectStatus, String> {
+    let workdir = repo.workdir().ok_or("No working directory found")?;
+    if !matches!(verdict, "good" | "bad" | "skip") {
+        return Err(format!("Unknown bisect verdict: {}", verdict));
+    }
+    if !bisect_status(repo)?.active {
+        return Err("No bisect in progress".to_string());
+    }
+    let sha = sha.map(|sha| bisect_commit_id(repo, sha)).transpose()?;
+    let mut args = vec!["bisect", verdict];
+    args.extend(sha.as_deref());
+    run_git_command(args, workdir.to_str(), vec![])
+        .map_err(|e| format!("Failed to mark commit as {}: {}", verdict, e))?;
+    bisect_status(repo)
+}
+
+/// Ends the bisect session and checks out the branch it was started from.
+pub fn bisect_reset(repo: &Repository) -> Result<(), String> {
+    let workdir = repo.workdir().ok_or("No working directory found")?;
+    run_git_command(vec!["bisect", "reset"], workdir.to_str(), vec![])
+        .map_err(|e| format!("Failed to reset bisect: {}", e))?;
+    Ok(())
+}
+
+/// Runs `command` through the shell at each bisect step and marks the commit from its exit
+/// code like `git bisect run`: 0 is good, 125 skips, 1-127 is bad, anything else stops.
+pub fn bisect_run(
+    repo: &Repository,
+    command: &str,
+    mut on_step: impl FnMut(&BisectStep),
+) -> Result<BisectStatus, String> {
+    let workdir = repo.workdir().ok_or("No working directory found")?;
+    let mut status = bisect_status(repo)?;
+    if !status.active {
+        return Err("No bisect in progress".to_string());
+    }
+    while status.first_bad.is_none() && status.remaining > 0 {
+        let sha = status.current.clone().unwrap_or_default();
+
+        #[cfg(windows)]
+        let mut shell = {
+            let mut c = Command::new("cmd");
+            c.arg("/C");
+            c
+        };
+        #[cfg(not(windows))]
+        let mut shell = {
+            let mut c = Command::new("sh");
+            c.arg("-c");
+            c
+        };
+        let output = shell
+            .arg(command)
+            .current_dir(workdir)
+            .output()
+            .map_err(|e| format!("Failed to run bisect command: {}", e))?;
+
+        let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        if text.len() > BISECT_OUTPUT_LIMIT {
+            let mut cut = text.len() - BISECT_OUTPUT_LIMIT;
+            while !text.is_char_boundary(cut) {
+                cut += 1;
+            }
+            text.drain(..cut);
+        }
+        let exit_code = output.status.code();
+        let verdict = match exit_code {
+            Some(0) => "good",
+            Some(125) => "skip",
+            Some(1..=127) => "bad",
+            _ => {
+                return Err(format!(
+                    "Bisect command stopped at {} with {}:\n{}",
+                    sha,
+                    output.status,
+                    text.trim()
+                ))
+            }
+        };
+        on_step(&BisectStep {
+            sha,
+            exit_code,
+            verdict: verdict.to_string(),
+            output: text.trim().to_string(),
+        });
+        status = bisect_mark(repo, verdict, None)?;
+    }
+    Ok(status)
+}
+
+/// Images above this size come back without their content.
+const IMAGE_INLINE_LIMIT: u64 = 20 * 1024 * 1024;
+
+/// MIME type for the image formats the diff view can render, by file extension.
+fn image_mime_type(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    match ext.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        "svg" => Some("image/svg+xml"),
+        _ => None,
+    }
+}
+
+/// Pixel size read from the image header; SVGs use their width/height or viewBox.
+fn image_dimensions(content: &[u8], mime: &str) -> Option<(u32, u32)> {
+    let be16 = |i: usize| Some(u16::from_be_bytes([*content.get(i)?, *content.get(i + 1)?]) as u32);
+    let le16 = |i: usize| Some(u16::from_le_bytes([*content.get(i)?, *content.get(i + 1)?]) as u32);
+    let le24 = |i: usize| Some(le16(i)? | (*content.get(i + 2)? as u32) << 16);
+    match mime {
+        "image/png" => {
+            if !content.starts_with(b"\x89PNG\r\n\x1a\n") || content.get(12..16)? != b"IHDR" {
+                return None;
+            }
+            let width = u32::from_be_bytes(content.get(16..20)?.try_into().ok()?);
+            let height = u32::from_be_bytes(content.get(20..24)?.try_into().ok()?);
+            Some((width, height))
+        }
+        "image/gif" => {
+            if !content.starts_with(b"GIF8") {
+                return None;
+            }
+            Some((le16(6)?, le16(8)?))
+        }
+        "image/jpeg" => {
+            if !content.starts_with(&[0xFF, 0xD8]) {
+                return None;
+            }
+            let mut i = 2;
+            while i + 3 < content.len() {
+                if content[i] != 0xFF {
+                    return None;
+                }
+                let marker = content[i + 1];
+                match marker {
+                    0xFF => i += 1, // fill byte
+                    0x01 | 0xD0..=0xD9 => i += 2,
+                    // Start-of-frame markers carry the size; C4, C8 and CC are other tables
+                    0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                        return Some((be16(i + 7)?, be16(i + 5)?));
+                    }
+                    _ => i += 2 + be16(i + 2)? as usize,
+                }
+            }
+            None
+        }
+        "image/webp" => {
+            if content.get(0..4)? != b"RIFF" || content.get(8..12)? != b"WEBP" {
+                return None;
+            }
+            match content.get(12..16)? {
+                b"VP8 " => Some((le16(26)? & 0x3FFF, le16(28)? & 0x3FFF)),
+                b"VP8L" => {
+                    let b = content.get(21..25)?;
+                    let width = 1 + (b[0] as u32 | ((b[1] as u32 & 0x3F) << 8));
+                    let height = 1
+                        + ((b[1] as u32 >> 6) | ((b[2] as u32) << 2) | ((b[3] as u32 & 0x0F) << 10));
+                    Some((width, height))
+                }
+                b"VP8X" => Some((1 + le24(24)?, 1 + le24(27)?)),
+                _ => None,
+            }
+        }
+        "image/svg+xml" => svg_dimensions(&String::from_utf8_lossy(content)),
+        _ => None,
+    }
+}
+
+fn svg_dimensions(text: &str) -> Option<(u32, u32)> {
+    let start = text.find("<svg")?;
+    let tag = &text[start..start + text[start..].find('>')?];
+    let attr = |name: &str| -> Option<&str> {
+        let mut offset = 0;
+        while let Some(pos) = tag[offset..].find(name) {
+            let at = offset + pos;
+            offset = at + name.len();
+            // Skip partial matches such as stroke-width
+            if !tag[..at].ends_with(char::is_whitespace) {
+                continue;
+            }
+            let rest = tag[offset..].trim_start().strip_prefix('=')?.trim_start();
+            let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+            let rest = &rest[1..];
+            return rest.find(quote).map(|end| &rest[..end]);
+        }
+        None
+    };
+    // Percentages and other relative units fall through to the viewBox
+    let length = |value: &str| -> Option<u32> {
+        let value = value.trim().trim_end_matches("px");
+        value.parse::<f64>().ok().filter(|v| *v > 0.0).map(|v| v.round() as u32)
+    };
+    let width = attr("width").and_then(length);
+    let height = attr("height").and_then(length);
+    if let (Some(width), Some(height)) = (width, height) {
+        return Some((width, height));
+    }
+    let view_box: Vec<f64> = attr("viewBox")?
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|v| !v.is_empty())
+        .filter_map(|v| v.parse().ok())
+        .collect();
+    match view_box.as_slice() {
+        [_, _, width, height] if *width > 0.0 && *height > 0.0 => {
+            Some((width.round() as u32, height.round() as u32))
+        }
+        _ => None,
+    }
+}
+
+/// One side of an image diff. Content above the inline limit is only sized, not read. LFS
+/// pointers are resolved to the downloaded object, or described by their size when it's missing.
+fn image_side(repo: &Repository, file: &git2::DiffFile, mime: &str) -> Option<ImageSide> {
+    if !file.exists() {
+        return None;
+    }
+    let mut size = diff_file_len(repo, file)?;
+    let mut content = if size <= IMAGE_INLINE_LIMIT {
+        Some(diff_file_content(repo, file)?)
+    } else {
+        None
+    };
+    if let Some((lfs_oid, lfs_size)) = content.as_deref().and_then(parse_lfs_pointer) {
+        size = lfs_size;
+        content = lfs_object_path(repo, &lfs_oid)
+            .filter(|path| lfs_size <= IMAGE_INLINE_LIMIT && path.is_file())
+            .and_then(|path| std::fs::read(path).ok());
+    }
+    let dimensions = content.as_deref().and_then(|c| image_dimensions(c, mime));
+    Some(ImageSide {
+        oid: (!file.id().is_zero()).then(|| file.id().to_string()),
+        size,
+        width: dimensions.map(|d| d.0),
+        height: dimensions.map(|d| d.1),
+        data: content.map(|c| BASE64.encode(c)),
+    })
+}
+
+/// Old and new versions of an image in a diff, base64-encoded with their sizes and dimensions,
+/// for side-by-side, swipe and onion-skin views.
+pub fn get_image_diff(
+    repo: &Repository,
+    source: &DiffSource,
+    path: &str,
+) -> Result<ImageDiff, String> {
+    let options = DiffViewOptions {
+        max_file_size: 0,
+        ..Default::default()
+    };
+    let diff = build_diff(repo, source, &options)?;
+    let delta = diff
+        .deltas()
+        .find(|d| delta_matches(d, path))
+        .ok_or_else(|| format!("'{}' is not part of this diff", path))?;
+    let mime = image_mime_type(Path::new(&delta_path(&delta)))
+        .or_else(|| image_mime_type(delta.old_file().path()?))
+        .ok_or_else(|| format!("'{}' is not a supported image", path))?;
+
+    Ok(ImageDiff {
+        path: delta_path(&delta),
+        status: delta_status_name(delta.status()).to_string(),
+        mime_type: mime.to_string(),
+        old: image_side(repo, &delta.old_file(), mime),
+        new: image_side(repo, &delta.new_file(), mime),
+    })
+}
+
+/// Lists LFS pointers in the index together with whether their objects are downloaded.
+pub fn list_lfs_files(repo: &Repository) -> Result<Vec<LfsFileInfo>, String> {
+    let index = repo
+        .index()
+        .map_err(|e| format!("Failed to get index: {}", e))?;
+
+    let mut files = Vec::new();
+    for entry in index.iter() {
+        let path = String::from_utf8_lossy(&entry.path).to_string();
+        if !is_lfs_tracked(repo, Path::new(&path)) {
+            continue;
+        }
+        let blob = match repo.find_blob(entry.id) {
+            Ok(b) => b,
+            Err(_) => continue,
+        };
+        if let Some((oid, size)) = parse_lfs_pointer(blob.content()) {
+            let present = lfs_object_present(repo, &oid);
+            files.push(LfsFileInfo { path, oid, size, present });
+        }
+    }
+    Ok(files)
+}
+
+/// Extracts the percentage from git-lfs progress output such as
+/// `Downloading LFS objects:  50% (1/2), 1.2 MB | 3 MB/s`.
+fn parse_lfs_percent(line: &str) -> Option<u32> {
+    let idx = line.find('%')?;
+    let digits: String = line[..idx]
+        .chars()
+        .rev()
+        .take_while(|c| c.is_ascii_digit())
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect();
+    digits.parse().ok()
+}
+
+/// Runs `git lfs fetch` or `git lfs pull` limited to `paths`, reporting progress lines as they arrive.
+pub fn run_lfs_command(
+    repo: &Repository,
+    operation: &str,
+    paths: &[String],
+    ssh_key_path: Option<&str>,
+    mut on_progress: impl FnMut(LfsProgress),
+) -> Result<(), String> {
+    if operation != "fetch" && operation != "pull" {
+        return Err(format!("Unsupported LFS operation '{}'", operation));
+    }
+    if paths.iter().any(|p| p.contains(',') || p.trim().is_empty()) {
+        return Err("Invalid LFS path".to_string());
+    }
+    let workdir = repo.workdir().ok_or("No working directory found")?;
+
+    let mut command = Command::new("git");
+    command.env("GIT_TERMINAL_PROMPT", "0");
+    command.args(["lfs", operation]);
+    if !paths.is_empty() {
+        command.arg(format!("--include={}", paths.join(",")));
+    }
+    command.current_dir(workdir);
+    for (key, val) in ssh_command_env(ssh_key_path) {
+        command.env(key, val);
+    }
+    command.stdout(std::process::Stdio::null());
+    command.stderr(std::process::Stdio::piped());
+
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("Failed to execute git lfs: {}", e))?;
+
+    // Progress is written to stderr and redrawn with carriage returns
+    let mut last_line = String::new();
+    if let Some(stderr) = child.stderr.take() {
+        use std::io::Read;
+        let mut current = Vec::new();
+        for byte in std::io::BufReader::new(stderr).bytes() {
+            let byte = byte.map_err(|e| format!("Failed to read git lfs output: {}", e))?;
+            if byte == b'\r' || byte == b'\n' {
+                let line = String::from_utf8_lossy(&current).trim().to_string();
+                current.clear();
+                if !line.is_empty() {
+                    on_progress(LfsProgress {
+                        operation: operation.to_string(),
+                        percent: parse_lfs_percent(&line),
+                        message: line.clone(),
+                    });
+                    last_line = line;
+                }
+            } else {
+                current.push(byte);
+            }
+        }
+    }
+
+    let status = child
+        .wait()
+        .map_err(|e| format!("Failed to wait for git lfs: {}", e))?;
+    if status.success() {
+        Ok(())
+    } else if last_line.contains("'lfs' is not a git command") {
+        Err("Git LFS is not installed".to_string())
+    } else if last_line.is_empty() {
+        Err(format!("git lfs {} failed with status: {}", operation, status))
+    } else {
+        Err(last_line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn get_temp_dir() -> PathBuf {
+        let mut path = std::env::temp_dir();
+        path.push("tauri_git_test");
+        path.push(format!("{}", std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos()));
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_pull_changes() {
+        // Setup origin
+        let root = get_temp_dir();
+        let origin_path = root.join("origin");
+        let local_path = root.join("local");
 
         fs::create_dir(&origin_path).unwrap();
         let _ = Repository::init(&origin_path).unwrap();
@@ -1001,15 +4668,1286 @@ mod tests {
         let file_path = root.join("file.txt");
         fs::write(&file_path, "v1").unwrap();
         run_git_command(vec!["add", "."], Some(root.to_str().unwrap()), vec![]).unwrap();
-        create_commit(&repo, "Initial commit").unwrap();
+        create_commit(&repo, "Initial commit", &repo.signature().unwrap(), true).unwrap();
 
         // Amend
-        let result = amend_last_commit(&repo, "Amended message");
+        let result = amend_last_commit(&repo, "Amended message", &repo.signature().unwrap(), false, true);
         assert!(result.is_ok());
 
         let head = repo.head().unwrap();
         let commit = head.peel_to_commit().unwrap();
-        assert_eq!(commit.message().unwrap(), "Amended message");
+        assert_eq!(commit.message().unwrap(), "Amended message\n");
+
+        // Authorship is kept unless explicitly reset
+        let other = Signature::now("Other Person", "other@example.com").unwrap();
+        amend_last_commit(&repo, &get_head_message(&repo).unwrap(), &other, false, true).unwrap();
+        let commit = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(commit.author().name(), Some("Test User"));
+        assert_eq!(commit.committer().name(), Some("Other Person"));
+        assert_eq!(commit.message().unwrap(), "Amended message\n");
+
+        amend_last_commit(&repo, "Amended message", &other, true, true).unwrap();
+        let commit = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(commit.author().name(), Some("Other Person"));
+        assert!(!is_head_pushed(&repo).unwrap());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_commit_hooks() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = get_temp_dir();
+        let repo = Repository::init(&root).unwrap();
+        run_git_command(vec!["config", "user.name", "Test User"], Some(root.to_str().unwrap()), vec![]).unwrap();
+        run_git_command(vec!["config", "user.email", "test@example.com"], Some(root.to_str().unwrap()), vec![]).unwrap();
+
+        let hooks = root.join("custom-hooks");
+        fs::create_dir_all(&hooks).unwrap();
+        run_git_command(vec!["config", "core.hooksPath", "custom-hooks"], Some(root.to_str().unwrap()), vec![]).unwrap();
+
+        let commit_msg = hooks.join("commit-msg");
+        fs::write(&commit_msg, "#!/bin/sh\nif grep -q WIP \"$1\"; then echo 'no WIP commits' >&2; exit 1; fi\necho 'Hooked: yes' >> \"$1\"\n").unwrap();
+        fs::set_permissions(&commit_msg, fs::Permissions::from_mode(0o755)).unwrap();
+
+        fs::write(root.join("file.txt"), "v1").unwrap();
+        run_git_command(vec!["add", "file.txt"], Some(root.to_str().unwrap()), vec![]).unwrap();
+
+        let err = create_commit(&repo, "WIP stuff", &repo.signature().unwrap(), false).unwrap_err();
+        assert!(err.contains("no WIP commits"), "unexpected error: {}", err);
+        assert!(repo.head().is_err());
+
+        create_commit(&repo, "Real commit\n", &repo.signature().unwrap(), false).unwrap();
+        let commit = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(commit.message().unwrap(), "Real commit\nHooked: yes\n");
+
+        // Skipping hooks lets the rejected message through untouched
+        amend_last_commit(&repo, "WIP stuff", &repo.signature().unwrap(), false, true).unwrap();
+        let commit = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(commit.message().unwrap(), "WIP stuff\n");
+
+        // --no-verify still runs prepare-commit-msg and post-commit
+        let prepare = hooks.join("prepare-commit-msg");
+        fs::write(&prepare, "#!/bin/sh\necho 'Prepared: yes' >> \"$1\"\n").unwrap();
+        fs::set_permissions(&prepare, fs::Permissions::from_mode(0o755)).unwrap();
+        let post_commit = hooks.join("post-commit");
+        fs::write(&post_commit, "#!/bin/sh\ntouch post-commit-ran\n").unwrap();
+        fs::set_permissions(&post_commit, fs::Permissions::from_mode(0o755)).unwrap();
+        amend_last_commit(&repo, "WIP stuff\n", &repo.signature().unwrap(), false, true).unwrap();
+        let commit = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(commit.message().unwrap(), "WIP stuff\nPrepared: yes\n");
+        assert!(root.join("post-commit-ran").exists());
+
+        // Files staged by pre-commit are committed; comments added by hooks are cleaned up
+        let pre_commit = hooks.join("pre-commit");
+        fs::write(&pre_commit, "#!/bin/sh\necho gen > gen.txt && git add gen.txt\n").unwrap();
+        fs::set_permissions(&pre_commit, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(&prepare, "#!/bin/sh\necho '# Please enter the commit message' >> \"$1\"\n").unwrap();
+        create_commit(&repo, "Generate   \n", &repo.signature().unwrap(), false).unwrap();
+        let commit = repo.head().unwrap().peel_to_commit().unwrap();
+        assert!(commit.tree().unwrap().get_path(Path::new("gen.txt")).is_ok());
+        assert_eq!(commit.message().unwrap(), "Generate\nHooked: yes\n");
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_resolve_identity() {
+        let root = get_temp_dir();
+        let repo = Repository::init(&root).unwrap();
+        repo.remote("origin", "git@github.example.com:acme/app.git").unwrap();
+
+        let settings = Settings {
+            user_name: String::new(),
+            user_email: String::new(),
+            ssh_key_path: None,
+            ssh_passphrase: None,
+            theme: "dark".to_string(),
+            recent_repositories: Vec::new(),
+            last_opened_repository: None,
+            identity_profiles: vec![IdentityProfile {
+                name: "work".to_string(),
+                user_name: "Work Me".to_string(),
+                user_email: "me@acme.example".to_string(),
+                path_prefix: None,
+                remote_host: Some("github.example.com".to_string()),
+            }],
+            allow_placeholder_identity: false,
+        };
+
+        let identity = resolve_identity(&repo, &settings).unwrap();
+        assert_eq!(identity.email, "me@acme.example");
+        assert_eq!(identity.source, "profile:work");
+
+        // Repository-local config beats profiles
+        run_git_command(vec!["config", "user.name", "Local Me"], Some(root.to_str().unwrap()), vec![]).unwrap();
+        run_git_command(vec!["config", "user.email", "local@example.org"], Some(root.to_str().unwrap()), vec![]).unwrap();
+        assert_eq!(resolve_identity(&repo, &settings).unwrap().source, "repository");
+
+        // A repository that doesn't exist yet matches profiles by its future remote
+        let new_path = root.join("not-yet").to_str().unwrap().to_string();
+        let identity = resolve_new_repo_identity(&new_path, Some("https://github.example.com/a/b.git"), &settings);
+        assert_eq!(identity.unwrap().source, "profile:work");
+        assert!(!root.join("not-yet").exists());
+
+        assert_eq!(remote_host("https://user@gitlab.com/group/project.git").as_deref(), Some("gitlab.com"));
+        assert_eq!(remote_host("ssh://git@host.example:2222/repo").as_deref(), Some("host.example"));
+
+        assert!(is_placeholder_identity(PLACEHOLDER_NAME, PLACEHOLDER_EMAIL));
+        assert!(is_placeholder_identity("Someone", ""));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_apply_trailers() {
+        let identity = CommitIdentity {
+            name: "Test User".to_string(),
+            email: "test@example.com".to_string(),
+            source: "settings".to_string(),
+        };
+        let co_author = CommitTrailer {
+            key: "Co-authored-by".to_string(),
+            value: "Pair <pair@example.com>".to_string(),
+        };
+
+        let message = apply_trailers("Fix parser\n", std::slice::from_ref(&co_author), Some(&identity)).unwrap();
+        assert_eq!(
+            message,
+            "Fix parser\n\nCo-authored-by: Pair <pair@example.com>\nSigned-off-by: Test User <test@example.com>\n"
+        );
+
+        // Re-applying joins the existing block without duplicating entries
+        let refs = CommitTrailer { key: "Refs".to_string(), value: "#42".to_string() };
+        let message = apply_trailers(&message, &[co_author, refs], Some(&identity)).unwrap();
+        assert!(message.ends_with("Signed-off-by: Test User <test@example.com>\nRefs: #42\n"));
+        assert_eq!(message.matches("Co-authored-by").count(), 1);
+
+        let bad = CommitTrailer { key: "Bad Key".to_string(), value: "x".to_string() };
+        assert!(apply_trailers("Subject", &[bad], None).is_err());
+    }
+
+    #[test]
+    fn test_lint_commit_message() {
+        let mut config = CommitLintConfig::default();
+        let result = lint_commit_message("Fix parser\nDetails right after subject", &config);
+        assert_eq!(result.violations.len(), 1);
+        assert_eq!(result.violations[0].rule, "body-leading-blank");
+        assert!(!result.blocking);
+
+        config.conventional = true;
+        config.scopes = vec!["ui".to_string()];
+        config.enforce = true;
+        assert!(lint_commit_message("feat(ui)!: add dark mode\n\nBody", &config).violations.is_empty());
+
+        let result = lint_commit_message("feature(api): add endpoint", &config);
+        let rules: Vec<&str> = result.violations.iter().map(|v| v.rule.as_str()).collect();
+        assert_eq!(rules, vec!["type-enum", "scope-enum"]);
+        assert!(result.blocking);
+
+        let config: CommitLintConfig = serde_json::from_str(r#"{"subject_max_length": 10}"#).unwrap();
+        assert_eq!(lint_commit_message("A subject that is long", &config).violations[0].rule, "subject-max-length");
+
+        // A commitlint YAML config is not ours to read and must not block commits
+        let root = get_temp_dir();
+        let repo = Repository::init(&root).unwrap();
+        fs::write(root.join(".commitlintrc"), "extends:\n  - '@commitlint/config-conventional'\n").unwrap();
+        assert!(!load_commit_lint_config(&repo).unwrap().enforce);
+        fs::write(root.join(COMMIT_LINT_CONFIG_FILE), r#"{"enforce": true}"#).unwrap();
+        assert!(load_commit_lint_config(&repo).unwrap().enforce);
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_init_repository() {
+        let root = get_temp_dir().join("new-project");
+        let repo = init_repository(root.to_str().unwrap(), "trunk", Some("https://example.com/new.git")).unwrap();
+        let written = write_starter_files(&repo, true, Some("rust"), Some("MIT"), "Test User").unwrap();
+        assert_eq!(written, vec!["README.md", ".gitignore", "LICENSE"]);
+        assert_eq!(fs::read_to_string(root.join("README.md")).unwrap(), "# new-project\n");
+        let license = fs::read_to_string(root.join("LICENSE")).unwrap();
+        assert!(license.contains(&format!("Copyright (c) {} Test User", current_year())));
+
+        let info = get_repository_info(&repo).unwrap();
+        assert_eq!(info.current_branch, "trunk");
+        assert_eq!(get_remote_url(&repo, "origin").unwrap(), "https://example.com/new.git");
+
+        assert!(init_repository(root.to_str().unwrap(), "main", None).is_err());
+        assert!(write_starter_files(&repo, false, Some("Cobol"), None, "").is_err());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_clone_options() {
+        let root = get_temp_dir();
+        let origin_path = root.join("origin");
+        let origin = origin_path.to_str().unwrap();
+        fs::create_dir(&origin_path).unwrap();
+        run_git_command(vec!["init"], Some(origin), vec![]).unwrap();
+        run_git_command(vec!["config", "user.name", "Test User"], Some(origin), vec![]).unwrap();
+        run_git_command(vec!["config", "user.email", "test@example.com"], Some(origin), vec![]).unwrap();
+        run_git_command(vec!["commit", "--allow-empty", "-m", "First"], Some(origin), vec![]).unwrap();
+        run_git_command(vec!["checkout", "-b", "feature"], Some(origin), vec![]).unwrap();
+        run_git_command(vec!["commit", "--allow-empty", "-m", "Second"], Some(origin), vec![]).unwrap();
+
+        let local_path = root.join("local");
+        let mut options = CloneOptions {
+            url: format!("file://{}", origin),
+            path: local_path.to_str().unwrap().to_string(),
+            branch: Some("feature".to_string()),
+            depth: Some(1),
+            single_branch: true,
+            filter: None,
+            bare: false,
+            mirror: false,
+            recurse_submodules: false,
+        };
+        let local = clone_repository(&options, None, None).unwrap();
+        assert!(local.is_shallow());
+        assert_eq!(local.head().unwrap().shorthand(), Some("feature"));
+
+        // The target now has content, so a second clone must be refused
+        assert!(clone_repository(&options, None, None).is_err());
+
+        options.path = root.join("other").to_str().unwrap().to_string();
+        options.filter = Some("blob:everything".to_string());
+        assert!(clone_repository(&options, None, None).is_err());
+        assert!(is_valid_clone_filter("blob:limit=10k"));
+        assert!(is_valid_clone_filter("tree:0"));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_submodules() {
+        let root = get_temp_dir();
+        let lib_path = root.join("lib");
+        let app_path = root.join("app");
+        for dir in [&lib_path, &app_path] {
+            let dir = dir.to_str().unwrap();
+            fs::create_dir_all(dir).unwrap();
+            run_git_command(vec!["init"], Some(dir), vec![]).unwrap();
+            run_git_command(vec!["config", "user.name", "Test User"], Some(dir), vec![]).unwrap();
+            run_git_command(vec!["config", "user.email", "test@example.com"], Some(dir), vec![]).unwrap();
+            run_git_command(vec!["commit", "--allow-empty", "-m", "Initial commit"], Some(dir), vec![]).unwrap();
+        }
+        let app = app_path.to_str().unwrap();
+        run_git_command(
+            vec!["-c", "protocol.file.allow=always", "submodule", "add", lib_path.to_str().unwrap(), "vendor/lib"],
+            Some(app),
+            vec![],
+        )
+        .unwrap();
+        run_git_command(vec!["commit", "-m", "Add lib"], Some(app), vec![]).unwrap();
+
+        let repo = Repository::open(&app_path).unwrap();
+        let subs = list_submodules(&repo).unwrap();
+        assert_eq!(subs.len(), 1);
+        assert_eq!(subs[0].path, "vendor/lib");
+        assert!(subs[0].initialized);
+        assert!(!subs[0].dirty);
+        assert_eq!(subs[0].recorded_commit, subs[0].checked_out_commit);
+
+        fs::write(app_path.join("vendor/lib/new.txt"), "x").unwrap();
+        assert!(list_submodules(&repo).unwrap()[0].dirty);
+        let status = get_status(&repo, false).unwrap();
+        assert!(status.iter().any(|f| f.path == "vendor/lib" && f.is_submodule));
+
+        assert!(get_submodule_path(&repo, "vendor/lib").unwrap().ends_with("vendor/lib"));
+        assert!(get_submodule_path(&repo, "src").is_err());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_worktrees() {
+        let root = get_temp_dir();
+        let main_path = root.join("main");
+        let main = main_path.to_str().unwrap();
+        fs::create_dir_all(main).unwrap();
+        run_git_command(vec!["init"], Some(main), vec![]).unwrap();
+        run_git_command(vec!["config", "user.name", "Test User"], Some(main), vec![]).unwrap();
+        run_git_command(vec!["config", "user.email", "test@example.com"], Some(main), vec![]).unwrap();
+        run_git_command(vec!["commit", "--allow-empty", "-m", "Initial commit"], Some(main), vec![]).unwrap();
+
+        let repo = Repository::open(&main_path).unwrap();
+        let review_path = root.join("review");
+        add_worktree(&repo, review_path.to_str().unwrap(), "review-pr", true, None).unwrap();
+
+        let worktrees = list_worktrees(&repo).unwrap();
+        assert_eq!(worktrees.len(), 2);
+        assert!(worktrees[0].is_main && worktrees[0].is_current);
+        assert_eq!(worktrees[1].branch.as_deref(), Some("review-pr"));
+        assert!(!worktrees[1].is_current);
+
+        // Opened from the linked side, the worktree knows where the main checkout lives
+        let linked = Repository::open(&review_path).unwrap();
+        let info = get_repository_info(&linked).unwrap();
+        assert!(info.is_worktree);
+        assert_eq!(info.main_repository_path.as_deref(), Some(worktrees[0].path.as_str()));
+        assert!(list_worktrees(&linked).unwrap()[1].is_current);
+
+        assert!(remove_worktree(&repo, main, false).is_err());
+        remove_worktree(&repo, review_path.to_str().unwrap(), false).unwrap();
+        assert_eq!(list_worktrees(&repo).unwrap().len(), 1);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_lfs_pointer_diff() {
+        let root = get_temp_dir();
+        let repo = Repository::init(&root).unwrap();
+        let dir = root.to_str().unwrap();
+        run_git_command(vec!["config", "user.name", "Test User"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["config", "user.email", "test@example.com"], Some(dir), vec![]).unwrap();
+
+        let oid = "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393";
+        let pointer = |oid: &str, size: u64| format!("{}\noid sha256:{}\nsize {}\n", LFS_POINTER_VERSION, oid, size);
+        fs::write(root.join(".gitattributes"), "*.psd filter=lfs diff=lfs merge=lfs -text\n").unwrap();
+        fs::write(root.join("art.psd"), pointer(oid, 12345)).unwrap();
+        run_git_command(vec!["add", "."], Some(dir), vec![]).unwrap();
+        create_commit(&repo, "Add art", &repo.signature().unwrap(), true).unwrap();
+
+        // Pretend the object was downloaded
+        let object_dir = root.join(".git/lfs/objects").join(&oid[0..2]).join(&oid[2..4]);
+        fs::create_dir_all(&object_dir).unwrap();
+        fs::write(object_dir.join(oid), "binary").unwrap();
+
+        let new_oid = "b".repeat(64);
+        fs::write(root.join("art.psd"), pointer(&new_oid, 999)).unwrap();
+        let diffs = get_diff(&repo, None, &DiffViewOptions::default()).unwrap();
+        let lfs = diffs.iter().find(|d| d.path == "art.psd").unwrap().lfs.clone().unwrap();
+        let old = lfs.old.unwrap();
+        assert_eq!(old.oid.as_deref(), Some(oid));
+        assert_eq!(old.size, 12345);
+        assert!(old.present);
+        assert!(!lfs.new.unwrap().present);
+
+        // A smudged working-tree file is sized from its metadata
+        fs::write(root.join("art.psd"), vec![0u8; 4096]).unwrap();
+        let diffs = get_diff(&repo, None, &DiffViewOptions::default()).unwrap();
+        let lfs = diffs.iter().find(|d| d.path == "art.psd").unwrap().lfs.clone().unwrap();
+        let new = lfs.new.unwrap();
+        assert_eq!(new.oid, None);
+        assert_eq!(new.size, 4096);
+
+        let files = list_lfs_files(&repo).unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].present);
+
+        assert_eq!(parse_lfs_percent("Downloading LFS objects:  50% (1/2), 1.2 MB | 3 MB/s"), Some(50));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_status_model() {
+        let root = get_temp_dir();
+        let repo = Repository::init(&root).unwrap();
+        let dir = root.to_str().unwrap();
+        run_git_command(vec!["config", "user.name", "Test User"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["config", "user.email", "test@example.com"], Some(dir), vec![]).unwrap();
+        fs::write(root.join("a.txt"), "a1\n").unwrap();
+        fs::write(root.join("b.txt"), "some content that is long enough to detect a rename\n").unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        run_git_command(vec!["add", "."], Some(dir), vec![]).unwrap();
+        create_commit(&repo, "Init", &repo.signature().unwrap(), true).unwrap();
+
+        fs::write(root.join("a.txt"), "a2\n").unwrap();
+        run_git_command(vec!["add", "a.txt"], Some(dir), vec![]).unwrap();
+        fs::write(root.join("a.txt"), "a3\n").unwrap();
+        run_git_command(vec!["mv", "b.txt", "c.txt"], Some(dir), vec![]).unwrap();
+        fs::write(root.join("debug.log"), "x").unwrap();
+
+        let status = get_status(&repo, true).unwrap();
+        let find = |p: &str| status.iter().find(|f| f.path == p).unwrap();
+
+        let a = find("a.txt");
+        assert_eq!(a.index_status.as_deref(), Some("modified"));
+        assert_eq!(a.worktree_status.as_deref(), Some("modified"));
+        assert!(a.staged);
+
+        let c = find("c.txt");
+        assert_eq!(c.status, "renamed");
+        assert_eq!(c.old_path.as_deref(), Some("b.txt"));
+        assert!(!status.iter().any(|f| f.path == "b.txt"));
+
+        assert!(find("debug.log").ignored);
+        assert!(!get_status(&repo, false).unwrap().iter().any(|f| f.ignored));
+
+        // Unstaging the rename leaves it in the working tree; staging it again moves both paths
+        unstage_files(&repo, vec!["c.txt".to_string()]).unwrap();
+        let status = get_status(&repo, false).unwrap();
+        let c = status.iter().find(|f| f.path == "c.txt").unwrap();
+        assert_eq!(c.worktree_status.as_deref(), Some("renamed"));
+        assert_eq!(c.index_status, None);
+        let result = stage_files(&repo, vec!["c.txt".to_string()]).unwrap();
+        assert_eq!(result.staged, vec!["c.txt", "b.txt"]);
+        assert!(result.warnings.is_empty());
+        create_commit(&repo, "Rename", &repo.signature().unwrap(), false).unwrap();
+        let tree = repo.head().unwrap().peel_to_tree().unwrap();
+        assert!(tree.get_path(Path::new("c.txt")).is_ok());
+        assert!(tree.get_path(Path::new("b.txt")).is_err());
+        assert!(!get_status(&repo, false).unwrap().iter().any(|f| f.path == "b.txt"));
+
+        // Discarding a working-tree rename restores the old name
+        fs::rename(root.join("c.txt"), root.join("d.txt")).unwrap();
+        discard_paths(&repo, vec!["d.txt".to_string()]).unwrap();
+        assert!(root.join("c.txt").exists());
+        assert!(!root.join("d.txt").exists());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_fast_status() {
+        let root = get_temp_dir();
+        let repo = Repository::init(&root).unwrap();
+        let dir = root.to_str().unwrap();
+        run_git_command(vec!["config", "user.name", "Test User"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["config", "user.email", "test@example.com"], Some(dir), vec![]).unwrap();
+        fs::write(root.join("a.txt"), "a1\n").unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        run_git_command(vec!["add", "."], Some(dir), vec![]).unwrap();
+        create_commit(&repo, "Init", &repo.signature().unwrap(), true).unwrap();
+
+        fs::write(root.join("debug.log"), "x").unwrap();
+        assert!(!is_dirty(&repo).unwrap());
+
+        fs::write(root.join("a.txt"), "a2 changed\n").unwrap();
+        assert!(is_dirty(&repo).unwrap());
+        run_git_command(vec!["checkout", "--", "a.txt"], Some(dir), vec![]).unwrap();
+        assert!(!is_dirty(&repo).unwrap());
+
+        // A same-size edit in the instant the index was written keeps the stat data
+        let entry = repo.index().unwrap().get_path(Path::new("a.txt"), 0).unwrap();
+        let stamp = std::time::UNIX_EPOCH
+            + std::time::Duration::new(entry.mtime.seconds() as u64, entry.mtime.nanoseconds());
+        fs::write(root.join("a.txt"), "a9\n").unwrap();
+        for path in [root.join("a.txt"), root.join(".git/index")] {
+            fs::File::options().write(true).open(path).unwrap().set_modified(stamp).unwrap();
+        }
+        assert!(is_dirty(&Repository::open(&root).unwrap()).unwrap());
+        run_git_command(vec!["checkout", "--", "a.txt"], Some(dir), vec![]).unwrap();
+
+        let mut cache = StatusCache::new(true);
+        assert!(cache.get(&repo, false).unwrap().is_empty());
+
+        fs::write(root.join("new.txt"), "n").unwrap();
+        assert!(is_dirty(&repo).unwrap());
+        // Unreported paths stay hidden until the watcher marks them
+        assert!(cache.get(&repo, false).unwrap().is_empty());
+        cache.mark_paths(vec!["new.txt".to_string()]);
+        let status = cache.get(&repo, false).unwrap();
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].path, "new.txt");
+
+        // Staging changes the index, which forces a full rescan
+        run_git_command(vec!["add", "new.txt"], Some(dir), vec![]).unwrap();
+        assert!(cache.get(&repo, false).unwrap()[0].staged);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_repository_state_name() {
+        let root = get_temp_dir();
+        let repo = Repository::init(&root).unwrap();
+        let dir = root.to_str().unwrap();
+        run_git_command(vec!["config", "user.name", "Test User"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["config", "user.email", "test@example.com"], Some(dir), vec![]).unwrap();
+        fs::write(root.join("a.txt"), "base\n").unwrap();
+        run_git_command(vec!["add", "."], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["commit", "-m", "Init"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["checkout", "-b", "other"], Some(dir), vec![]).unwrap();
+        fs::write(root.join("a.txt"), "other\n").unwrap();
+        run_git_command(vec!["commit", "-am", "Other"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["checkout", "-"], Some(dir), vec![]).unwrap();
+        fs::write(root.join("a.txt"), "main\n").unwrap();
+        run_git_command(vec!["commit", "-am", "Main"], Some(dir), vec![]).unwrap();
+
+        assert_eq!(repository_state_name(repo.state()), "clean");
+        assert!(run_git_command(vec!["merge", "other"], Some(dir), vec![]).is_err());
+        assert_eq!(repository_state_name(repo.state()), "merge");
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_discard_hunks_and_lines() {
+        let root = get_temp_dir();
+        let repo = Repository::init(&root).unwrap();
+        let dir = root.to_str().unwrap();
+        run_git_command(vec!["config", "user.name", "Test User"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["config", "user.email", "test@example.com"], Some(dir), vec![]).unwrap();
+        let original: String = (1..=12).map(|i| format!("line {}\n", i)).collect();
+        fs::write(root.join("a.txt"), &original).unwrap();
+        run_git_command(vec!["add", "."], Some(dir), vec![]).unwrap();
+        create_commit(&repo, "Init", &repo.signature().unwrap(), true).unwrap();
+
+        let edited = original.replace("line 2\n", "line two\n").replace("line 11\n", "line eleven\n");
+        fs::write(root.join("a.txt"), &edited).unwrap();
+        let options = DiscardHunksOptions {
+            path: "a.txt".to_string(),
+            hunks: vec![0],
+            lines: vec![],
+            diff_options: DiffViewOptions::default(),
+        };
+        let result = discard_hunks(&repo, &options).unwrap();
+        let content = fs::read_to_string(root.join("a.txt")).unwrap();
+        assert!(content.contains("line 2\n") && content.contains("line eleven\n"));
+
+        // The discarded edit is recoverable from the snapshot
+        let snapshot_ref = result.snapshot_ref.unwrap();
+        restore_discarded(&repo, &snapshot_ref, vec!["a.txt".to_string()]).unwrap();
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), edited);
+
+        // Line granularity: drop one of two added lines
+        fs::write(root.join("a.txt"), format!("{}extra 1\nextra 2\n", original)).unwrap();
+        let options = DiscardHunksOptions {
+            path: "a.txt".to_string(),
+            hunks: vec![],
+            lines: vec![crate::models::LineSelection { old_lineno: None, new_lineno: Some(14) }],
+            diff_options: DiffViewOptions::default(),
+        };
+        discard_hunks(&repo, &options).unwrap();
+        assert_eq!(
+            fs::read_to_string(root.join("a.txt")).unwrap(),
+            format!("{}extra 1\n", original)
+        );
+
+        // A selection that matches no changed line is an error, not a partial discard
+        let options = DiscardHunksOptions {
+            path: "a.txt".to_string(),
+            hunks: vec![],
+            lines: vec![
+                crate::models::LineSelection { old_lineno: None, new_lineno: Some(13) },
+                crate::models::LineSelection { old_lineno: None, new_lineno: Some(99) },
+            ],
+            diff_options: DiffViewOptions::default(),
+        };
+        assert!(discard_hunks(&repo, &options).is_err());
+        assert_eq!(
+            fs::read_to_string(root.join("a.txt")).unwrap(),
+            format!("{}extra 1\n", original)
+        );
+
+        // Indices follow the unstaged diff: with line 2 staged, hunk 0 is the line 11 edit
+        fs::write(root.join("a.txt"), &edited).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("a.txt")).unwrap();
+        index.write().unwrap();
+        fs::write(root.join("a.txt"), edited.replace("line 5\n", "line five\n")).unwrap();
+        let options = DiscardHunksOptions {
+            path: "a.txt".to_string(),
+            hunks: vec![0],
+            lines: vec![],
+            diff_options: DiffViewOptions::default(),
+        };
+        discard_hunks(&repo, &options).unwrap();
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), edited);
+        let head = repo.head().unwrap().peel(git2::ObjectType::Commit).unwrap();
+        repo.reset_default(Some(&head), ["a.txt"]).unwrap();
+
+        // Filters are honoured: CRLF checkouts keep their line endings
+        run_git_command(vec!["config", "core.autocrlf", "true"], Some(dir), vec![]).unwrap();
+        let repo = Repository::open(&root).unwrap(); // libgit2 caches core.autocrlf per handle
+        let crlf = |text: &str| text.replace('\n', "\r\n");
+        fs::write(root.join("a.txt"), crlf(&edited)).unwrap();
+        let options = DiscardHunksOptions {
+            path: "a.txt".to_string(),
+            hunks: vec![1],
+            lines: vec![],
+            diff_options: DiffViewOptions::default(),
+        };
+        discard_hunks(&repo, &options).unwrap();
+        assert_eq!(
+            fs::read_to_string(root.join("a.txt")).unwrap(),
+            crlf(&original.replace("line 2\n", "line two\n"))
+        );
+        run_git_command(vec!["config", "core.autocrlf", "false"], Some(dir), vec![]).unwrap();
+        let repo = Repository::open(&root).unwrap();
+
+        fs::write(root.join("new.txt"), "untracked").unwrap();
+        let result = discard_paths(&repo, vec!["a.txt".to_string(), "new.txt".to_string()]).unwrap();
+        assert!(result.files.iter().all(|f| f.discarded));
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), original);
+        assert!(!root.join("new.txt").exists());
+        let snapshot = repo
+            .find_reference(&result.snapshot_ref.unwrap())
+            .unwrap()
+            .peel_to_tree()
+            .unwrap();
+        assert!(snapshot.get_path(Path::new("new.txt")).is_ok());
+
+        // Ignored files inside an untracked directory aren't snapshotted
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        fs::create_dir_all(root.join("out")).unwrap();
+        fs::write(root.join("out/keep.txt"), "keep").unwrap();
+        fs::write(root.join("out/build.log"), "noise").unwrap();
+        let snapshot_ref = snapshot_worktree_files(&repo, "discard", &["out".to_string()]).unwrap();
+        let snapshot = repo.find_reference(&snapshot_ref.unwrap()).unwrap().peel_to_tree().unwrap();
+        assert!(snapshot.get_path(Path::new("out/keep.txt")).is_ok());
+        assert!(snapshot.get_path(Path::new("out/build.log")).is_err());
+        fs::remove_dir_all(root.join("out")).unwrap();
+        fs::remove_file(root.join(".gitignore")).unwrap();
+
+        // Restoring everything only writes the saved files; other edits survive
+        fs::write(root.join("a.txt"), "unrelated edit\n").unwrap();
+        fs::write(root.join("b.txt"), "b").unwrap();
+        let result = discard_paths(&repo, vec!["b.txt".to_string()]).unwrap();
+        restore_discarded(&repo, result.snapshot_ref.as_ref().unwrap(), vec![]).unwrap();
+        assert_eq!(fs::read_to_string(root.join("b.txt")).unwrap(), "b");
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "unrelated edit\n");
+        let snapshot_ref = result.snapshot_ref.unwrap();
+        assert!(restore_discarded(&repo, &snapshot_ref, vec!["a.txt".to_string()]).is_err());
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "unrelated edit\n");
+
+        // Executable files come back with their exec bit
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::write(root.join("run.sh"), "#!/bin/sh\n").unwrap();
+            fs::set_permissions(root.join("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+            let result = discard_paths(&repo, vec!["run.sh".to_string()]).unwrap();
+            assert!(!root.join("run.sh").exists());
+            restore_discarded(&repo, result.snapshot_ref.as_ref().unwrap(), vec![]).unwrap();
+            let mode = fs::metadata(root.join("run.sh")).unwrap().permissions().mode();
+            assert_ne!(mode & 0o111, 0);
+        }
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_intraline_highlights() {
+        let spans = |ranges: &[TextRange], text: &str| -> Vec<String> {
+            ranges.iter().map(|r| text[r.start..r.end].to_string()).collect()
+        };
+
+        let (old, new) = intraline_ranges("timeout = 30 # seconds", "timeout = 45 # seconds", "word");
+        assert_eq!(spans(&old, "timeout = 30 # seconds"), vec!["30"]);
+        assert_eq!(spans(&new, "timeout = 45 # seconds"), vec!["45"]);
+
+        let (old, new) = intraline_ranges("maxRetries", "maxRetry", "char");
+        assert_eq!(spans(&old, "maxRetries"), vec!["ies"]);
+        assert_eq!(spans(&new, "maxRetry"), vec!["y"]);
+
+        // Offsets count UTF-16 units, so a surrogate pair is two wide
+        let (_, new) = intraline_ranges("a 😀 b", "a 😀 c", "word");
+        assert_eq!((new[0].start, new[0].end), (5, 6));
+
+        let root = get_temp_dir();
+        let repo = Repository::init(&root).unwrap();
+        let dir = root.to_str().unwrap();
+        run_git_command(vec!["config", "user.name", "Test User"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["config", "user.email", "test@example.com"], Some(dir), vec![]).unwrap();
+        fs::write(root.join("config.json"), "{\"a\": 1, \"b\": 2}\n").unwrap();
+        run_git_command(vec!["add", "."], Some(dir), vec![]).unwrap();
+        create_commit(&repo, "Init", &repo.signature().unwrap(), true).unwrap();
+        fs::write(root.join("config.json"), "{\"a\": 1, \"b\": 3}\n").unwrap();
+
+        let diffs = get_diff(&repo, None, &DiffViewOptions::default()).unwrap();
+        let highlights = &diffs[0].highlights;
+        assert_eq!(highlights.len(), 2);
+        assert_eq!(highlights[0].old_lineno, Some(1));
+        assert_eq!(highlights[1].new_lineno, Some(1));
+        assert_eq!((highlights[1].ranges[0].start, highlights[1].ranges[0].end), (14, 15));
+        let options = DiffViewOptions {
+            intraline: "none".to_string(),
+            ..Default::default()
+        };
+        assert!(get_diff(&repo, None, &options).unwrap()[0].highlights.is_empty());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_diff_view_options() {
+        let root = get_temp_dir();
+        let repo = Repository::init(&root).unwrap();
+        let dir = root.to_str().unwrap();
+        run_git_command(vec!["config", "user.name", "Test User"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["config", "user.email", "test@example.com"], Some(dir), vec![]).unwrap();
+        let original: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+        fs::write(root.join("a.txt"), &original).unwrap();
+        fs::write(root.join("old.txt"), "content that stays the same across the rename\n").unwrap();
+        run_git_command(vec!["add", "."], Some(dir), vec![]).unwrap();
+        create_commit(&repo, "Init", &repo.signature().unwrap(), true).unwrap();
+
+        fs::write(root.join("a.txt"), original.replace("line 10\n", "line    10\n")).unwrap();
+        let defaults = DiffViewOptions::default();
+        let diff = &get_diff(&repo, Some("a.txt"), &defaults).unwrap()[0];
+        assert_eq!((diff.additions, diff.deletions), (1, 1));
+        assert_eq!(diff.diff_text.lines().filter(|l| l.starts_with(' ')).count(), 6);
+
+        let ignore_change = DiffViewOptions {
+            ignore_whitespace: "change".to_string(),
+            ..Default::default()
+        };
+        let diff = &get_diff(&repo, Some("a.txt"), &ignore_change).unwrap()[0];
+        assert_eq!((diff.additions, diff.deletions), (0, 0));
+
+        let more_context = DiffViewOptions {
+            context_lines: 5,
+            ..Default::default()
+        };
+        let diff = &get_diff(&repo, Some("a.txt"), &more_context).unwrap()[0];
+        assert_eq!(diff.diff_text.lines().filter(|l| l.starts_with(' ')).count(), 10);
+
+        let bad = DiffViewOptions {
+            algorithm: "quantum".to_string(),
+            ..Default::default()
+        };
+        assert!(get_diff(&repo, None, &bad).is_err());
+        let histogram = DiffViewOptions {
+            algorithm: "histogram".to_string(),
+            ..Default::default()
+        };
+        assert!(get_diff(&repo, None, &histogram).is_err());
+
+        // Hidden binary content still leaves the file in the list
+        fs::write(root.join("blob.bin"), b"\x00\x01\x02").unwrap();
+        let hide_binary = DiffViewOptions {
+            show_binary: false,
+            ..Default::default()
+        };
+        let unstaged = get_unstaged_diff(&repo, None, &hide_binary).unwrap();
+        let blob = unstaged.iter().find(|d| d.path == "blob.bin").unwrap();
+        assert!(blob.binary);
+        assert!(blob.diff_text.is_empty());
+        fs::remove_file(root.join("blob.bin")).unwrap();
+
+        run_git_command(vec!["checkout", "--", "a.txt"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["mv", "old.txt", "new.txt"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["commit", "-m", "Rename"], Some(dir), vec![]).unwrap();
+        let sha = repo.head().unwrap().target().unwrap().to_string();
+        let diffs = get_commit_diff(&repo, &sha, 0, &defaults).unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].path, "new.txt");
+        let no_renames = DiffViewOptions {
+            detect_renames: false,
+            ..Default::default()
+        };
+        assert_eq!(get_commit_diff(&repo, &sha, 0, &no_renames).unwrap().len(), 2);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_staged_and_unstaged_diffs() {
+        let root = get_temp_dir();
+        let repo = Repository::init(&root).unwrap();
+        let dir = root.to_str().unwrap();
+        run_git_command(vec!["config", "user.name", "Test User"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["config", "user.email", "test@example.com"], Some(dir), vec![]).unwrap();
+        fs::write(root.join("a.txt"), "one\n").unwrap();
+        run_git_command(vec!["add", "."], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["commit", "-m", "Init"], Some(dir), vec![]).unwrap();
+
+        fs::write(root.join("a.txt"), "two\n").unwrap();
+        run_git_command(vec!["add", "a.txt"], Some(dir), vec![]).unwrap();
+        fs::write(root.join("a.txt"), "three\n").unwrap();
+        fs::write(root.join("new.txt"), "fresh\n").unwrap();
+
+        let options = DiffViewOptions::default();
+        let staged = get_staged_diff(&repo, None, &options).unwrap();
+        assert_eq!(staged.len(), 1);
+        assert!(staged[0].diff_text.contains("-one\n") && staged[0].diff_text.contains("+two\n"));
+
+        let unstaged = get_unstaged_diff(&repo, None, &options).unwrap();
+        let a = unstaged.iter().find(|d| d.path == "a.txt").unwrap();
+        assert!(a.diff_text.contains("-two\n") && a.diff_text.contains("+three\n"));
+        let new = unstaged.iter().find(|d| d.path == "new.txt").unwrap();
+        assert_eq!(new.additions, 1);
+
+        let combined = get_diff(&repo, Some("a.txt"), &options).unwrap();
+        assert!(combined[0].diff_text.contains("-one\n") && combined[0].diff_text.contains("+three\n"));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_compare_revisions() {
+        let root = get_temp_dir();
+        let repo = Repository::init(&root).unwrap();
+        let dir = root.to_str().unwrap();
+        run_git_command(vec!["config", "user.name", "Test User"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["config", "user.email", "test@example.com"], Some(dir), vec![]).unwrap();
+        fs::write(root.join("a.txt"), "base\n").unwrap();
+        run_git_command(vec!["add", "."], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["commit", "-m", "Init"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["branch", "-M", "main"], Some(dir), vec![]).unwrap();
+
+        run_git_command(vec!["checkout", "-b", "feature"], Some(dir), vec![]).unwrap();
+        fs::write(root.join("feature.txt"), "feature\n").unwrap();
+        run_git_command(vec!["add", "."], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["commit", "-m", "Feature work"], Some(dir), vec![]).unwrap();
+
+        run_git_command(vec!["checkout", "main"], Some(dir), vec![]).unwrap();
+        fs::write(root.join("a.txt"), "changed on main\n").unwrap();
+        run_git_command(vec!["commit", "-am", "Main work"], Some(dir), vec![]).unwrap();
+
+        let options = DiffViewOptions::default();
+        // Two-dot: everything that differs between the tips
+        let direct = compare_revisions(&repo, "main", "feature", false, &options).unwrap();
+        assert_eq!(direct.files.len(), 2);
+        assert!(direct.merge_base.is_none());
+        assert_eq!(direct.only_in_from[0].message.trim(), "Main work");
+        assert_eq!(direct.only_in_to[0].message.trim(), "Feature work");
+        assert_eq!((direct.only_in_from_count, direct.only_in_to_count), (1, 1));
+
+        // Three-dot: only what the feature branch adds
+        let pr = compare_revisions(&repo, "main...feature", "", false, &options).unwrap();
+        assert!(pr.merge_base.is_some());
+        assert_eq!(pr.files.len(), 1);
+        assert_eq!(pr.files[0].path, "feature.txt");
+        assert_eq!(pr.files[0].status, "added");
+        assert_eq!(pr.files[0].additions, Some(1));
+        assert_eq!(pr.diffs.len(), 1);
+
+        assert!(compare_revisions(&repo, "main", "nope", false, &options).is_err());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_merge_commit_diffs() {
+        let root = get_temp_dir();
+        let repo = Repository::init(&root).unwrap();
+        let dir = root.to_str().unwrap();
+        run_git_command(vec!["config", "user.name", "Test User"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["config", "user.email", "test@example.com"], Some(dir), vec![]).unwrap();
+        fs::write(root.join("a.txt"), "base\n").unwrap();
+        fs::write(root.join("b.txt"), "b\n").unwrap();
+        run_git_command(vec!["add", "."], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["commit", "-m", "Init"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["branch", "-M", "main"], Some(dir), vec![]).unwrap();
+
+        run_git_command(vec!["checkout", "-b", "other"], Some(dir), vec![]).unwrap();
+        fs::write(root.join("a.txt"), "theirs\n").unwrap();
+        fs::write(root.join("b.txt"), "b changed\n").unwrap();
+        run_git_command(vec!["commit", "-am", "Other"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["checkout", "main"], Some(dir), vec![]).unwrap();
+        fs::write(root.join("a.txt"), "ours\n").unwrap();
+        run_git_command(vec!["commit", "-am", "Main"], Some(dir), vec![]).unwrap();
+
+        assert!(run_git_command(vec!["merge", "other"], Some(dir), vec![]).is_err());
+        fs::write(root.join("a.txt"), "resolved\n").unwrap();
+        run_git_command(vec!["commit", "-am", "Merge other"], Some(dir), vec![]).unwrap();
+        let sha = repo.head().unwrap().target().unwrap().to_string();
+
+        let options = DiffViewOptions::default();
+        let first = get_commit_diff(&repo, &sha, 0, &options).unwrap();
+        assert_eq!(first.len(), 2); // a.txt resolved and b.txt brought in from "other"
+        let second = get_commit_diff(&repo, &sha, 1, &options).unwrap();
+        assert_eq!(second.len(), 1);
+        assert!(second[0].diff_text.contains("-theirs"));
+        assert!(get_commit_diff(&repo, &sha, 2, &options).is_err());
+
+        // Only the conflicted file was changed relative to both parents
+        let condensed = get_combined_diff(&repo, &sha, true, &options).unwrap();
+        assert_eq!(condensed.len(), 1);
+        assert_eq!(condensed[0].path, "a.txt");
+        assert_eq!(condensed[0].additions, 1);
+        assert!(condensed[0].diff_text.contains("++resolved"));
+        assert!(condensed[0].diff_text.ends_with('\n'));
+
+        // Diff options are validated and applied like for the other diffs
+        let histogram = DiffViewOptions {
+            algorithm: "histogram".to_string(),
+            ..Default::default()
+        };
+        assert!(get_combined_diff(&repo, &sha, true, &histogram).is_err());
+        let limited = DiffViewOptions {
+            max_diff_lines: 1,
+            ..Default::default()
+        };
+        let condensed = get_combined_diff(&repo, &sha, true, &limited).unwrap();
+        assert!(condensed[0].too_large && condensed[0].diff_text.is_empty());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_two_phase_diff() {
+        let root = get_temp_dir();
+        let repo = Repository::init(&root).unwrap();
+        let dir = root.to_str().unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Test User").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+
+        fs::write(root.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+        fs::write(root.join("big.txt"), "x\n".repeat(2000)).unwrap();
+        fs::write(root.join("old.txt"), "stays the same\nacross the rename\n").unwrap();
+        run_git_command(vec!["add", "-A"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["commit", "-m", "init"], Some(dir), vec![]).unwrap();
+
+        fs::write(root.join("a.txt"), "one\n2\nthree\nfour\n").unwrap();
+        fs::write(root.join("big.txt"), "y\n".repeat(2000)).unwrap();
+        run_git_command(vec!["mv", "old.txt", "new.txt"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["add", "-A"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["commit", "-m", "change"], Some(dir), vec![]).unwrap();
+        let sha = repo.head().unwrap().target().unwrap().to_string();
+
+        let source = DiffSource {
+            kind: "commit".to_string(),
+            sha: Some(sha),
+            parent: 0,
+        };
+        let options = DiffViewOptions {
+            max_file_size: 1024,
+            ..Default::default()
+        };
+        let list = get_diff_files(&repo, &source, &options).unwrap();
+        assert_eq!(list.stats.files_changed, 3);
+        assert_eq!(list.stats.insertions, 2);
+        assert_eq!(list.stats.deletions, 1);
+        let big = list.files.iter().find(|f| f.path == "big.txt").unwrap();
+        assert!(big.too_large);
+        assert_eq!(big.additions, None);
+        let renamed = list.files.iter().find(|f| f.path == "new.txt").unwrap();
+        assert_eq!(renamed.status, "renamed");
+        assert_eq!(renamed.old_path.as_deref(), Some("old.txt"));
+
+        let file = get_file_diff(&repo, &source, "a.txt", &options).unwrap();
+        assert_eq!((file.file.additions, file.file.deletions), (Some(2), Some(1)));
+        assert_eq!(file.hunks.len(), 1);
+        let origins: String = file.hunks[0].lines.iter().map(|l| l.origin.as_str()).collect();
+        assert_eq!(origins, " -+ +");
+        assert_eq!(file.hunks[0].lines[2].content, "2");
+        assert!(get_file_diff(&repo, &source, "big.txt", &options).unwrap().hunks.is_empty());
+        // Looking a rename up by its old path finds the same delta
+        let renamed = get_file_diff(&repo, &source, "old.txt", &options).unwrap();
+        assert_eq!(renamed.file.path, "new.txt");
+
+        let unlimited = DiffViewOptions {
+            max_file_size: 0,
+            ..Default::default()
+        };
+        let list = get_diff_files(&repo, &source, &unlimited).unwrap();
+        assert_eq!(list.stats.insertions, 2002);
+        let infos = get_commit_diff(&repo, source.sha.as_deref().unwrap(), 0, &options).unwrap();
+        assert!(infos.iter().any(|i| i.path == "big.txt" && i.too_large && i.diff_text.is_empty()));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_image_diff() {
+        let root = get_temp_dir();
+        let repo = Repository::init(&root).unwrap();
+        let dir = root.to_str().unwrap();
+        run_git_command(vec!["config", "user.name", "Test User"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["config", "user.email", "test@example.com"], Some(dir), vec![])
+            .unwrap();
+
+        // Only the header matters for dimensions
+        let png = |width: u32, height: u32| {
+            let mut bytes = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+            bytes.extend_from_slice(&width.to_be_bytes());
+            bytes.extend_from_slice(&height.to_be_bytes());
+            bytes.extend_from_slice(&[8, 6, 0, 0, 0, 0, 0, 0, 0]);
+            bytes
+        };
+        let old_png = png(3, 2);
+        fs::write(root.join("logo.png"), &old_png).unwrap();
+        run_git_command(vec!["add", "."], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["commit", "-m", "Init"], Some(dir), vec![]).unwrap();
+
+        fs::write(root.join("logo.png"), png(640, 480)).unwrap();
+        fs::write(
+            root.join("icon.svg"),
+            r#"<svg xmlns="http://www.w3.org/2000/svg" stroke-width="2" viewBox="0 0 24 16"/>"#,
+        )
+        .unwrap();
+
+        let source = DiffSource {
+            kind: "workdir".to_string(),
+            sha: None,
+            parent: 0,
+        };
+        let list = get_diff_files(&repo, &source, &DiffViewOptions::default()).unwrap();
+        assert!(list.files.iter().all(|f| f.image));
+
+        let image = get_image_diff(&repo, &source, "logo.png").unwrap();
+        assert_eq!(image.mime_type, "image/png");
+        let old = image.old.unwrap();
+        assert_eq!((old.width, old.height), (Some(3), Some(2)));
+        assert_eq!(old.size, old_png.len() as u64);
+        assert_eq!(old.data.unwrap(), BASE64.encode(&old_png));
+        let new = image.new.unwrap();
+        assert_eq!((new.width, new.height), (Some(640), Some(480)));
+
+        // Untracked files only show up in the unstaged diff
+        let unstaged = DiffSource {
+            kind: "unstaged".to_string(),
+            ..source
+        };
+        let svg = get_image_diff(&repo, &unstaged, "icon.svg").unwrap();
+        assert_eq!(svg.status, "untracked");
+        assert!(svg.old.is_none());
+        let new = svg.new.unwrap();
+        assert_eq!((new.width, new.height), (Some(24), Some(16)));
+
+        // An LFS pointer shows the downloaded object, or only its size before the download
+        let lfs_png = png(8, 4);
+        let lfs_oid = "c".repeat(64);
+        let pointer = format!("{}\noid sha256:{}\nsize 4096\n", LFS_POINTER_VERSION, lfs_oid);
+        fs::write(root.join("art.png"), pointer).unwrap();
+        let art = get_image_diff(&repo, &unstaged, "art.png").unwrap().new.unwrap();
+        assert_eq!(art.size, 4096);
+        assert!(art.data.is_none() && art.width.is_none());
+        let object = lfs_object_path(&repo, &lfs_oid).unwrap();
+        fs::create_dir_all(object.parent().unwrap()).unwrap();
+        fs::write(&object, &lfs_png).unwrap();
+        let art = get_image_diff(&repo, &unstaged, "art.png").unwrap().new.unwrap();
+        assert_eq!((art.width, art.height), (Some(8), Some(4)));
+        assert_eq!(art.data.unwrap(), BASE64.encode(&lfs_png));
+
+        let jpeg = [
+            0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, 0xFF, 0xC0, 0x00, 0x0B, 0x08, 0x00,
+            0x20, 0x00, 0x40, 0x01, 0x01, 0x11, 0x00,
+        ];
+        assert_eq!(image_dimensions(&jpeg, "image/jpeg"), Some((64, 32)));
+        assert_eq!(image_dimensions(b"GIF89a\x0a\x00\x05\x00", "image/gif"), Some((10, 5)));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_patch_export_and_apply() {
+        let root = get_temp_dir();
+        let repo = Repository::init(&root).unwrap();
+        let dir = root.to_str().unwrap();
+        run_git_command(vec!["config", "user.name", "Test User"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["config", "user.email", "test@example.com"], Some(dir), vec![])
+            .unwrap();
+
+        fs::write(root.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+        run_git_command(vec!["add", "."], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["commit", "-m", "Init"], Some(dir), vec![]).unwrap();
+        fs::write(root.join("a.txt"), "one\n2\nthree\n").unwrap();
+        let author = vec![
+            ("GIT_AUTHOR_NAME", "Partner".to_string()),
+            ("GIT_AUTHOR_EMAIL", "partner@example.com".to_string()),
+        ];
+        run_git_command(vec!["commit", "-am", "Change two"], Some(dir), author).unwrap();
+        fs::write(root.join("b.txt"), "b\n").unwrap();
+        run_git_command(vec!["add", "b.txt"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["commit", "-m", "Add b"], Some(dir), vec![]).unwrap();
+        let mbox = root.join("series.mbox");
+        let mbox_path = mbox.to_str().unwrap();
+        // Selected newest first, exported oldest first
+        let selection = ["HEAD".to_string(), "HEAD~1".to_string()];
+        assert_eq!(export_commit_patches(&repo, &selection, mbox_path).unwrap(), 2);
+        run_git_command(vec!["reset", "--hard", "HEAD~2"], Some(dir), vec![]).unwrap();
+
+        let committer = CommitIdentity {
+            name: "Test User".to_string(),
+            email: "test@example.com".to_string(),
+            source: "settings".to_string(),
+        };
+        let mut options = ApplyPatchOptions {
+            path: mbox_path.to_string(),
+            target: "am".to_string(),
+            check_only: true,
+        };
+        let result = apply_patch(&repo, &options, Some(&committer)).unwrap();
+        assert!(!result.applied);
+        assert!(result.failures.is_empty());
+        assert_eq!(result.files, vec!["a.txt", "b.txt"]);
+        options.check_only = false;
+        assert!(apply_patch(&repo, &options, Some(&committer)).unwrap().applied);
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.summary(), Some("Add b"));
+        let parent = head.parent(0).unwrap();
+        assert_eq!(parent.summary(), Some("Change two"));
+        assert_eq!(parent.author().name(), Some("Partner"));
+
+        // Working-tree patch round trip, unaffected by diff config that breaks `git apply`
+        run_git_command(vec!["config", "color.ui", "always"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["config", "diff.noprefix", "true"], Some(dir), vec![]).unwrap();
+        fs::write(root.join("a.txt"), "one\n2\n3\n").unwrap();
+        let patch = root.join("changes.patch");
+        let patch_path = patch.to_str().unwrap();
+        let source = DiffSource {
+            kind: "workdir".to_string(),
+            sha: None,
+            parent: 0,
+        };
+        export_diff_patch(&repo, &source, patch_path).unwrap();
+        run_git_command(vec!["checkout", "--", "a.txt"], Some(dir), vec![]).unwrap();
+        let options = ApplyPatchOptions {
+            path: patch_path.to_string(),
+            target: "worktree".to_string(),
+            check_only: false,
+        };
+        assert!(apply_patch(&repo, &options, None).unwrap().applied);
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "one\n2\n3\n");
+
+        // A conflicting file reports the failing hunk and is left untouched
+        run_git_command(vec!["checkout", "--", "a.txt"], Some(dir), vec![]).unwrap();
+        fs::write(root.join("a.txt"), "ONE\nTWO\nTHREE\n").unwrap();
+        let result = apply_patch(&repo, &options, None).unwrap();
+        assert!(!result.applied);
+        assert_eq!(result.failures.len(), 1);
+        assert_eq!(result.failures[0].path, "a.txt");
+        assert_eq!(result.failures[0].line, Some(1));
+        assert!(result.failures[0].hunk.as_deref().unwrap().starts_with("@@ -1,3 +1,3 @@"));
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "ONE\nTWO\nTHREE\n");
+
+        let _ = fs::remove_dir_all(root);
+        let _ = fs::remove_file(mbox);
+        let _ = fs::remove_file(patch);
+    }
+
+    #[test]
+    fn test_search_commits() {
+        let root = get_temp_dir();
+        let repo = Repository::init(&root).unwrap();
+        let dir = root.to_str().unwrap();
+        run_git_command(vec!["config", "user.name", "Test User"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["config", "user.email", "test@example.com"], Some(dir), vec![])
+            .unwrap();
+
+        fs::write(root.join("a.rs"), "fn main() {}\n").unwrap();
+        run_git_command(vec!["add", "."], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["commit", "-m", "Init"], Some(dir), vec![]).unwrap();
+        fs::write(root.join("a.rs"), "const MAX_RETRIES: u32 = 3;\nfn main() {}\n").unwrap();
+        let author = vec![
+            ("GIT_AUTHOR_NAME", "Alice Example".to_string()),
+            ("GIT_AUTHOR_EMAIL", "alice@example.com".to_string()),
+        ];
+        run_git_command(vec!["commit", "-am", "Add retry limit"], Some(dir), author).unwrap();
+        fs::write(root.join("a.rs"), "const MAX_RETRIES: u32 = 5;\nfn main() {}\n").unwrap();
+        run_git_command(vec!["commit", "-am", "Bump retries"], Some(dir), vec![]).unwrap();
+
+        let search = |options: CommitSearchOptions| {
+            let mut pages = Vec::new();
+            let summary =
+                search_commits(&repo, &options, || false, |page, _| pages.push(page)).unwrap();
+            (summary, pages)
+        };
+        let messages = |pages: &[Vec<CommitSearchMatch>]| -> Vec<String> {
+            pages.iter().flatten().map(|m| m.commit.message.trim().to_string()).collect()
+        };
+
+        let (summary, pages) = search(CommitSearchOptions {
+            query: "ALICE".to_string(),
+            ..Default::default()
+        });
+        assert_eq!(summary.total, 1);
+        assert_eq!(summary.scanned, 3);
+        assert_eq!(pages[0][0].matched, vec!["author"]);
+
+        // -S finds where the constant appeared, -G every change touching it
+        let (_, pages) = search(CommitSearchOptions {
+            query: "MAX_RETRIES".to_string(),
+            diff: "string".to_string(),
+            case_sensitive: true,
+            ..Default::default()
+        });
+        assert_eq!(messages(&pages), vec!["Add retry limit"]);
+        let (summary, pages) = search(CommitSearchOptions {
+            query: r"RETRIES: u32 = \d".to_string(),
+            diff: "regex".to_string(),
+            page_size: 1,
+            ..Default::default()
+        });
+        assert_eq!(summary.total, 2);
+        assert_eq!(pages.len(), 2);
+        assert_eq!(messages(&pages), vec!["Bump retries", "Add retry limit"]);
+
+        let head = repo.head().unwrap().target().unwrap().to_string();
+        let (_, pages) = search(CommitSearchOptions {
+            query: head[..7].to_uppercase(),
+            ..Default::default()
+        });
+        assert_eq!(pages[0][0].commit.sha, head);
+        assert!(pages[0][0].matched.contains(&"sha".to_string()));
+
+        // Discard snapshots aren't searched along with the branches and tags
+        fs::write(root.join("scratch.txt"), "scratch").unwrap();
+        snapshot_worktree_files(&repo, "discard", &["scratch.txt".to_string()]).unwrap();
+        let (summary, _) = search(CommitSearchOptions {
+            query: "safety snapshot".to_string(),
+            all_refs: true,
+            ..Default::default()
+        });
+        assert_eq!(summary.total, 0);
+        assert_eq!(summary.scanned, 3);
+
+        let summary = search_commits(
+            &repo,
+            &CommitSearchOptions {
+                query: "e".to_string(),
+                ..Default::default()
+            },
+            || true,
+            |_, _| panic!("no pages after cancellation"),
+        )
+        .unwrap();
+        assert!(summary.cancelled);
+        assert_eq!(summary.scanned, 0);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_bisect() {
+        let root = get_temp_dir();
+        let repo = Repository::init(&root).unwrap();
+        let dir = root.to_str().unwrap();
+        run_git_command(vec!["config", "user.name", "Test User"], Some(dir), vec![]).unwrap();
+        run_git_command(vec!["config", "user.email", "test@example.com"], Some(dir), vec![])
+            .unwrap();
+        run_git_command(vec!["checkout", "-b", "main"], Some(dir), vec![]).unwrap();
+
+        let mut shas = Vec::new();
+        for i in 0..8 {
+            let content = if i >= 5 { format!("v{} bug\n", i) } else { format!("v{}\n", i) };
+            fs::write(root.join("app.txt"), content).unwrap();
+            run_git_command(vec!["add", "."], Some(dir), vec![]).unwrap();
+            let message = format!("Commit {}", i);
+            run_git_command(vec!["commit", "-m", &message], Some(dir), vec![]).unwrap();
+            shas.push(repo.head().unwrap().target().unwrap().to_string());
+        }
+
+        // Option-like input is rejected instead of reaching git bisect
+        let bad_term = ["--term-bad=x".to_string()];
+        assert!(bisect_start(&repo, "--term-good=y", &shas[..1]).is_err());
+        assert!(bisect_start(&repo, &shas[7], &bad_term).is_err());
+        assert!(!bisect_status(&repo).unwrap().active);
+
+        let mut status = bisect_start(&repo, &shas[7], &shas[..1]).unwrap();
+        assert!(status.active);
+        assert_eq!(status.original_branch.as_deref(), Some("main"));
+        assert_eq!(status.remaining, 6);
+        assert_eq!(status.estimated_steps, 3);
+        while status.first_bad.is_none() {
+            let buggy = fs::read_to_string(root.join("app.txt")).unwrap().contains("bug");
+            status = bisect_mark(&repo, if buggy { "bad" } else { "good" }, None).unwrap();
+        }
+        assert_eq!(status.first_bad.unwrap().sha, shas[5]);
+        assert_eq!(status.remaining, 0);
+        bisect_reset(&repo).unwrap();
+        assert!(!bisect_status(&repo).unwrap().active);
+        assert_eq!(repo.head().unwrap().shorthand(), Some("main"));
+
+        bisect_start(&repo, &shas[7], &shas[..1]).unwrap();
+        let mut steps = Vec::new();
+        let status = bisect_run(&repo, "! grep -q bug app.txt", |step| steps.push(step.clone()))
+            .unwrap();
+        assert_eq!(status.first_bad.unwrap().sha, shas[5]);
+        assert!(!steps.is_empty());
+        assert!(steps.iter().all(|s| s.exit_code == Some(if s.verdict == "good" { 0 } else { 1 })));
+        bisect_reset(&repo).unwrap();
 
         let _ = fs::remove_dir_all(root);
     }
@@ -1025,7 +5963,7 @@ mod tests {
         
         fs::write(root.join("file.txt"), "v1").unwrap();
         run_git_command(vec!["add", "."], Some(root.to_str().unwrap()), vec![]).unwrap();
-        create_commit(&repo, "Init").unwrap();
+        create_commit(&repo, "Init", &repo.signature().unwrap(), true).unwrap();
 
         // Modify file
         fs::write(root.join("file.txt"), "v2").unwrap();
@@ -1,12 +1,17 @@
 mod git_operations;
 mod models;
+mod watcher;
 
 use models::{
-    BranchInfo, BranchOptions, CloneOptions, CommitInfo, CommitOptions, ConflictInfo, DiffInfo,
-    FileStatus, RepositoryInfo, Settings, StageResult, StashInfo, StashOptions,
+    AmendOptions, ApplyPatchOptions, BisectStatus, BranchInfo, BranchOptions, CloneOptions,
+    CoAuthor, CommitIdentity, CommitInfo, CommitLintResult, CommitOptions, CommitSearchOptions,
+    CommitSearchPage, CommitSearchSummary, ConflictInfo, DiffFileList, DiffInfo, DiffSource,
+    DiffViewOptions, DiscardHunksOptions, DiscardResult, FileDiff, FileStatus, ImageDiff,
+    InitOptions, InitTemplates, LfsFileInfo, PatchApplyResult, RepositoryInfo, RevisionComparison,
+    Settings, StageResult, StashInfo, StashOptions, SubmoduleInfo, WorktreeInfo, WorktreeOptions,
 };
-use notify::{Config, RecursiveMode, Watcher};
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use tauri::{Emitter, Manager, State};
 
 pub enum AppError {
@@ -52,48 +57,18 @@ impl From<&str> for AppError {
 struct AppState {
     repo: Option<git2::Repository>,
     settings: Settings,
-    watcher: Option<notify::RecommendedWatcher>,
+    watcher: Option<watcher::RepoWatcher>,
+    status_cache: git_operations::StatusCache,
+    active_search: Arc<AtomicU64>, // id of the running commit search; 0 when none
 }
 
 struct App(Mutex<AppState>);
 
 type AppResult<T> = Result<T, AppError>;
 
-fn start_watcher(app_handle: tauri::AppHandle, repo_path: &str) -> Option<notify::RecommendedWatcher> {
-    let path = std::path::Path::new(repo_path);
-    let git_path = path.join(".git");
-
-    if !git_path.exists() {
-        return None;
-    }
-
-    let (tx, rx) = std::sync::mpsc::channel();
-
-    let mut watcher = notify::RecommendedWatcher::new(tx, Config::default()).ok()?;
-
-    // Watch key git files for state changes
-    let _ = watcher.watch(&git_path.join("index"), RecursiveMode::NonRecursive);
-    let _ = watcher.watch(&git_path.join("HEAD"), RecursiveMode::NonRecursive);
-    let _ = watcher.watch(&git_path.join("refs"), RecursiveMode::Recursive);
-
-    std::thread::spawn(move || {
-        // Simple debounce: wait a bit and clear the channel of rapid events
-        while let Ok(res) = rx.recv() {
-            match res {
-                Ok(_) => {
-                    // Give Git a moment to finish its IO
-                    std::thread::sleep(std::time::Duration::from_millis(100));
-                    let _ = app_handle.emit("git-state-changed", ());
-
-                    // Drain the channel of immediate subsequent events
-                    while let Ok(_) = rx.try_recv() {}
-                }
-                Err(e) => eprintln!("watcher error: {:?}", e),
-            }
-        }
-    });
-
-    Some(watcher)
+/// Status can only be refreshed incrementally when the watcher sees working-tree edits.
+fn status_cache_for(watcher: &Option<watcher::RepoWatcher>) -> git_operations::StatusCache {
+    git_operations::StatusCache::new(watcher.as_ref().is_some_and(|w| w.watches_worktree))
 }
 
 fn get_settings_path(app_handle: &tauri::AppHandle) -> AppResult<std::path::PathBuf> {
@@ -132,7 +107,32 @@ fn load_settings_from_disk(app_handle: &tauri::AppHandle) -> Settings {
         theme: "dark".to_string(),
         recent_repositories: Vec::new(),
         last_opened_repository: None,
+        identity_profiles: Vec::new(),
+        allow_placeholder_identity: false,
+    }
+}
+
+/// Makes `repo` the open repository, starts watching it and records it as recently opened.
+fn set_open_repository(
+    state: &mut AppState,
+    app_handle: &tauri::AppHandle,
+    repo: git2::Repository,
+    path: &str,
+) -> AppResult<()> {
+    state.repo = Some(repo);
+    state.watcher = watcher::start_watcher(app_handle.clone(), path);
+    state.status_cache = status_cache_for(&state.watcher);
+
+    // Add to recent repositories if not already there
+    let path = path.to_string();
+    if !state.settings.recent_repositories.contains(&path) {
+        state.settings.recent_repositories.insert(0, path.clone());
+        if state.settings.recent_repositories.len() > 10 {
+            state.settings.recent_repositories.truncate(10);
+        }
     }
+    state.settings.last_opened_repository = Some(path);
+    save_settings_to_disk(state, app_handle)
 }
 
 #[tauri::command]
@@ -145,18 +145,7 @@ fn open_repository(
     match git_operations::open_repository(&path) {
         Ok(repo) => {
             let info = git_operations::get_repository_info(&repo)?;
-            state.repo = Some(repo);
-            state.watcher = start_watcher(app_handle.clone(), &path);
-            
-            // Add to recent repositories if not already there
-            if !state.settings.recent_repositories.contains(&path) {
-                state.settings.recent_repositories.insert(0, path.clone());
-                if state.settings.recent_repositories.len() > 10 {
-                    state.settings.recent_repositories.truncate(10);
-                }
-            }
-            state.settings.last_opened_repository = Some(path);
-            save_settings_to_disk(&state, &app_handle)?;
+            set_open_repository(&mut state, &app_handle, repo, &path)?;
             Ok(info)
         }
         Err(e) => {
@@ -173,6 +162,65 @@ fn open_repository(
     }
 }
 
+#[tauri::command]
+fn init_repository(
+    state: State<'_, App>,
+    app_handle: tauri::AppHandle,
+    options: InitOptions,
+) -> AppResult<RepositoryInfo> {
+    let mut state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let branch = options
+        .initial_branch
+        .as_deref()
+        .map(str::trim)
+        .filter(|b| !b.is_empty())
+        .unwrap_or("main");
+
+    // Everything that can fail without touching the disk goes first, so an error never leaves
+    // a half-initialized repository behind
+    git_operations::validate_init_templates(
+        options.gitignore_template.as_deref(),
+        options.license_template.as_deref(),
+    )?;
+    let identity = git_operations::resolve_new_repo_identity(
+        &options.path,
+        options.remote_url.as_deref(),
+        &state.settings,
+    );
+    let signature = match (&identity, options.initial_commit) {
+        (Ok(identity), true) => Some(git_operations::identity_signature(identity)?),
+        (Err(e), true) => return Err(AppError::Git(e.clone())),
+        (_, false) => None,
+    };
+    let holder = identity
+        .as_ref()
+        .map(|i| i.name.clone())
+        .unwrap_or_else(|_| state.settings.user_name.clone());
+
+    let repo = git_operations::init_repository(&options.path, branch, options.remote_url.as_deref())?;
+    let written = git_operations::write_starter_files(
+        &repo,
+        options.readme,
+        options.gitignore_template.as_deref(),
+        options.license_template.as_deref(),
+        &holder,
+    )?;
+
+    if let Some(signature) = signature {
+        git_operations::stage_files(&repo, written)?;
+        git_operations::create_commit(&repo, "Initial commit", &signature, false)?;
+    }
+
+    let info = git_operations::get_repository_info(&repo)?;
+    set_open_repository(&mut state, &app_handle, repo, &options.path)?;
+    Ok(info)
+}
+
+#[tauri::command]
+fn list_init_templates() -> InitTemplates {
+    git_operations::list_init_templates()
+}
+
 #[tauri::command]
 async fn clone_repository(
     state: State<'_, App>,
@@ -184,15 +232,12 @@ async fn clone_repository(
         (state_lock.settings.ssh_key_path.clone(), state_lock.settings.ssh_passphrase.clone())
     };
 
-    let url = options.url.clone();
     let path = options.path.clone();
 
     // Perform clone in a blocking thread to avoid freezing the async executor
-    let repo_path = path.clone();
     tauri::async_runtime::spawn_blocking(move || {
         git_operations::clone_repository(
-            &url,
-            &repo_path,
+            &options,
             ssh_key.as_deref(),
             ssh_pass.as_deref(),
         )
@@ -207,14 +252,7 @@ async fn clone_repository(
     // Re-open repo in state
     match git_operations::open_repository(&path) {
         Ok(repo) => {
-            state_lock.repo = Some(repo);
-            state_lock.watcher = start_watcher(app_handle.clone(), &path);
-
-            if !state_lock.settings.recent_repositories.contains(&path) {
-                state_lock.settings.recent_repositories.insert(0, path.clone());
-            }
-            state_lock.settings.last_opened_repository = Some(path.clone());
-            save_settings_to_disk(&state_lock, &app_handle)?;
+            set_open_repository(&mut state_lock, &app_handle, repo, &path)?;
             Ok(path)
         }
         Err(e) => Err(AppError::Git(e)),
@@ -222,21 +260,30 @@ async fn clone_repository(
 }
 
 #[tauri::command]
-fn get_repository_status(state: State<'_, App>) -> AppResult<Vec<FileStatus>> {
-    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+fn get_repository_status(state: State<'_, App>, include_ignored: Option<bool>) -> AppResult<Vec<FileStatus>> {
+    let mut state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let state = &mut *state;
     let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
-    git_operations::get_status(repo).map_err(AppError::Git)
+    state.status_cache.get(repo, include_ignored.unwrap_or(false)).map_err(AppError::Git)
 }
 
 #[tauri::command]
 fn create_commit(state: State<'_, App>, options: CommitOptions) -> AppResult<String> {
     let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
     let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    let identity = git_operations::resolve_identity(repo, &state.settings)?;
+    let signature = git_operations::identity_signature(&identity)?;
+    let message = git_operations::apply_trailers(
+        &options.message,
+        &options.trailers,
+        options.sign_off.then_some(&identity),
+    )?;
+    git_operations::enforce_commit_lint(repo, &message)?;
     let stage_result = git_operations::stage_files(repo, options.files)?;
     if stage_result.staged.is_empty() && !stage_result.warnings.is_empty() {
         return Err(AppError::Git(format!("No files could be staged: {}", stage_result.warnings.join("; "))));
     }
-    git_operations::create_commit(repo, &options.message).map_err(AppError::Git)
+    git_operations::create_commit(repo, &message, &signature, options.skip_hooks).map_err(AppError::Git)
 }
 
 #[tauri::command]
@@ -255,9 +302,42 @@ fn unstage_files(state: State<'_, App>, files: Vec<String>) -> AppResult<()> {
 
 #[tauri::command]
 fn discard_changes(state: State<'_, App>, file_path: String) -> AppResult<()> {
-    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let mut state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let state = &mut *state;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::discard_changes(repo, &file_path)?;
+    state.status_cache.mark_paths(vec![file_path]);
+    Ok(())
+}
+
+#[tauri::command]
+fn discard_paths(state: State<'_, App>, paths: Vec<String>) -> AppResult<DiscardResult> {
+    let mut state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let state = &mut *state;
     let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
-    git_operations::discard_changes(repo, &file_path).map_err(AppError::Git)
+    let result = git_operations::discard_paths(repo, paths)?;
+    state.status_cache.mark_paths(result.files.iter().map(|f| f.path.clone()));
+    Ok(result)
+}
+
+#[tauri::command]
+fn discard_hunks(state: State<'_, App>, options: DiscardHunksOptions) -> AppResult<DiscardResult> {
+    let mut state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let state = &mut *state;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    let result = git_operations::discard_hunks(repo, &options)?;
+    state.status_cache.mark_paths(vec![options.path]);
+    Ok(result)
+}
+
+#[tauri::command]
+fn restore_discarded(state: State<'_, App>, snapshot_ref: String, paths: Vec<String>) -> AppResult<()> {
+    let mut state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let state = &mut *state;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::restore_discarded(repo, &snapshot_ref, paths.clone())?;
+    state.status_cache.mark_paths(paths);
+    Ok(())
 }
 
 #[tauri::command]
@@ -282,10 +362,30 @@ fn checkout_branch(state: State<'_, App>, options: BranchOptions) -> AppResult<(
 }
 
 #[tauri::command]
-fn get_commit_diff(state: State<'_, App>, sha: String) -> AppResult<Vec<DiffInfo>> {
+fn get_commit_diff(
+    state: State<'_, App>,
+    sha: String,
+    parent: Option<usize>,
+    options: Option<DiffViewOptions>,
+) -> AppResult<Vec<DiffInfo>> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::get_commit_diff(repo, &sha, parent.unwrap_or(0), &options.unwrap_or_default())
+        .map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn get_combined_diff(
+    state: State<'_, App>,
+    sha: String,
+    condensed: Option<bool>,
+    options: Option<DiffViewOptions>,
+) -> AppResult<Vec<DiffInfo>> {
     let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
     let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
-    git_operations::get_commit_diff(repo, &sha).map_err(AppError::Git)
+    let options = options.unwrap_or_default();
+    git_operations::get_combined_diff(repo, &sha, condensed.unwrap_or(true), &options)
+        .map_err(AppError::Git)
 }
 
 #[tauri::command]
@@ -296,10 +396,130 @@ fn get_commit_history(state: State<'_, App>, limit: usize) -> AppResult<Vec<Comm
 }
 
 #[tauri::command]
-fn get_diff(state: State<'_, App>, file_path: Option<String>) -> AppResult<Vec<DiffInfo>> {
+fn get_diff(
+    state: State<'_, App>,
+    file_path: Option<String>,
+    options: Option<DiffViewOptions>,
+) -> AppResult<Vec<DiffInfo>> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::get_diff(repo, file_path.as_deref(), &options.unwrap_or_default()).map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn compare_revisions(
+    state: State<'_, App>,
+    from: String,
+    to: String,
+    merge_base: Option<bool>,
+    options: Option<DiffViewOptions>,
+) -> AppResult<RevisionComparison> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::compare_revisions(
+        repo,
+        &from,
+        &to,
+        merge_base.unwrap_or(false),
+        &options.unwrap_or_default(),
+    )
+    .map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn get_staged_diff(
+    state: State<'_, App>,
+    file_path: Option<String>,
+    options: Option<DiffViewOptions>,
+) -> AppResult<Vec<DiffInfo>> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::get_staged_diff(repo, file_path.as_deref(), &options.unwrap_or_default())
+        .map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn get_diff_files(
+    state: State<'_, App>,
+    source: DiffSource,
+    options: Option<DiffViewOptions>,
+) -> AppResult<DiffFileList> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::get_diff_files(repo, &source, &options.unwrap_or_default())
+        .map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn get_file_diff(
+    state: State<'_, App>,
+    source: DiffSource,
+    path: String,
+    options: Option<DiffViewOptions>,
+) -> AppResult<FileDiff> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::get_file_diff(repo, &source, &path, &options.unwrap_or_default())
+        .map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn get_image_diff(state: State<'_, App>, source: DiffSource, path: String) -> AppResult<ImageDiff> {
     let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
     let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
-    git_operations::get_diff(repo, file_path.as_deref()).map_err(AppError::Git)
+    git_operations::get_image_diff(repo, &source, &path).map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn export_commit_patches(
+    state: State<'_, App>,
+    shas: Vec<String>,
+    output_path: String,
+) -> AppResult<usize> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::export_commit_patches(repo, &shas, &output_path).map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn export_diff_patch(
+    state: State<'_, App>,
+    source: DiffSource,
+    output_path: String,
+) -> AppResult<()> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::export_diff_patch(repo, &source, &output_path).map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn apply_patch(state: State<'_, App>, options: ApplyPatchOptions) -> AppResult<PatchApplyResult> {
+    let mut state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let state = &mut *state;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    // Only "am" creates commits, so checks and worktree/index applies work without an identity
+    let identity = if options.target == "am" && !options.check_only {
+        Some(git_operations::resolve_identity(repo, &state.settings)?)
+    } else {
+        None
+    };
+    let result = git_operations::apply_patch(repo, &options, identity.as_ref())?;
+    if result.applied {
+        state.status_cache.invalidate();
+    }
+    Ok(result)
+}
+
+#[tauri::command]
+fn get_unstaged_diff(
+    state: State<'_, App>,
+    file_path: Option<String>,
+    options: Option<DiffViewOptions>,
+) -> AppResult<Vec<DiffInfo>> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::get_unstaged_diff(repo, file_path.as_deref(), &options.unwrap_or_default())
+        .map_err(AppError::Git)
 }
 
 #[tauri::command]
@@ -368,8 +588,11 @@ async fn fetch_changes(state: State<'_, App>) -> AppResult<()> {
 #[tauri::command]
 fn stash_save(state: State<'_, App>, options: StashOptions) -> AppResult<()> {
     let mut state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let state = &mut *state;
     let repo = state.repo.as_mut().ok_or(AppError::Git("No repository open".to_string()))?;
-    git_operations::stash_save(repo, options.message.as_deref()).map_err(AppError::Git)
+    let identity = git_operations::resolve_identity(repo, &state.settings)?;
+    let signature = git_operations::identity_signature(&identity)?;
+    git_operations::stash_save(repo, options.message.as_deref(), &signature).map_err(AppError::Git)
 }
 
 #[tauri::command]
@@ -401,31 +624,345 @@ fn resolve_conflict(state: State<'_, App>, path: String, use_ours: bool) -> AppR
 }
 
 #[tauri::command]
-fn amend_commit(state: State<'_, App>, message: String) -> AppResult<String> {
+fn amend_commit(state: State<'_, App>, options: AmendOptions) -> AppResult<String> {
     let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
     let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
-    git_operations::amend_last_commit(repo, &message).map_err(AppError::Git)
+    if !options.allow_pushed && git_operations::is_head_pushed(repo)? {
+        return Err(AppError::Git("The last commit has already been pushed. Amending it will rewrite published history.".to_string()));
+    }
+    let identity = git_operations::resolve_identity(repo, &state.settings)?;
+    let signature = git_operations::identity_signature(&identity)?;
+    let base_message = if options.keep_message {
+        git_operations::get_head_message(repo)?
+    } else {
+        options.message
+    };
+    let message = git_operations::apply_trailers(
+        &base_message,
+        &options.trailers,
+        options.sign_off.then_some(&identity),
+    )?;
+    git_operations::enforce_commit_lint(repo, &message)?;
+    if !options.files.is_empty() {
+        let stage_result = git_operations::stage_files(repo, options.files)?;
+        if stage_result.staged.is_empty() && !stage_result.warnings.is_empty() {
+            return Err(AppError::Git(format!("No files could be staged: {}", stage_result.warnings.join("; "))));
+        }
+    }
+    git_operations::amend_last_commit(repo, &message, &signature, options.reset_author, options.skip_hooks)
+        .map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn is_head_pushed(state: State<'_, App>) -> AppResult<bool> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::is_head_pushed(repo).map_err(AppError::Git)
 }
 
 #[tauri::command]
 fn cherry_pick(state: State<'_, App>, sha: String) -> AppResult<()> {
     let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
     let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
-    git_operations::cherry_pick(repo, &sha).map_err(AppError::Git)
+    let identity = git_operations::resolve_identity(repo, &state.settings)?;
+    let signature = git_operations::identity_signature(&identity)?;
+    git_operations::cherry_pick(repo, &sha, &signature).map_err(AppError::Git)
 }
 
 #[tauri::command]
 fn revert_commit(state: State<'_, App>, sha: String) -> AppResult<()> {
     let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
     let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
-    git_operations::revert_commit(repo, &sha).map_err(AppError::Git)
+    let identity = git_operations::resolve_identity(repo, &state.settings)?;
+    let signature = git_operations::identity_signature(&identity)?;
+    git_operations::revert_commit(repo, &sha, &signature).map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn get_commit_identity(state: State<'_, App>) -> AppResult<CommitIdentity> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::resolve_identity(repo, &state.settings).map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn get_commit_template(state: State<'_, App>) -> AppResult<Option<String>> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::get_commit_template(repo).map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn lint_commit_message(state: State<'_, App>, message: String) -> AppResult<CommitLintResult> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    let config = git_operations::load_commit_lint_config(repo)?;
+    Ok(git_operations::lint_commit_message(&message, &config))
+}
+
+#[tauri::command]
+fn get_coauthor_candidates(state: State<'_, App>, limit: usize) -> AppResult<Vec<CoAuthor>> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::get_coauthor_candidates(repo, limit).map_err(AppError::Git)
 }
 
 #[tauri::command]
 fn discard_all_changes(state: State<'_, App>) -> AppResult<()> {
+    let mut state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let state = &mut *state;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::discard_all_changes(repo)?;
+    state.status_cache.invalidate();
+    Ok(())
+}
+
+#[tauri::command]
+fn list_submodules(state: State<'_, App>) -> AppResult<Vec<SubmoduleInfo>> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::list_submodules(repo).map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn init_submodules(state: State<'_, App>, paths: Vec<String>) -> AppResult<()> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::init_submodules(repo, &paths).map_err(AppError::Git)
+}
+
+#[tauri::command]
+async fn update_submodules(state: State<'_, App>, paths: Vec<String>, recursive: bool) -> AppResult<()> {
+    let (path, ssh_key) = {
+        let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+        let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+        let path = repo.workdir().ok_or(AppError::Git("No workdir".to_string()))?.to_path_buf();
+        (path, state.settings.ssh_key_path.clone())
+    };
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let repo = git_operations::open_repository(path.to_str().ok_or("Invalid path")?)?;
+        git_operations::update_submodules(&repo, &paths, recursive, ssh_key.as_deref())
+            .map_err(AppError::Git)
+    })
+    .await
+    .map_err(|e| AppError::Git(format!("Spawn error: {}", e)))?
+}
+
+#[tauri::command]
+fn sync_submodules(state: State<'_, App>, recursive: bool) -> AppResult<()> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::sync_submodules(repo, recursive).map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn open_submodule(
+    state: State<'_, App>,
+    app_handle: tauri::AppHandle,
+    path: String,
+) -> AppResult<RepositoryInfo> {
+    let full_path = {
+        let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+        let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+        git_operations::get_submodule_path(repo, &path)?
+    };
+    open_repository(state, app_handle, full_path)
+}
+
+#[tauri::command]
+fn list_worktrees(state: State<'_, App>) -> AppResult<Vec<WorktreeInfo>> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::list_worktrees(repo).map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn add_worktree(state: State<'_, App>, options: WorktreeOptions) -> AppResult<Vec<WorktreeInfo>> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::add_worktree(
+        repo,
+        &options.path,
+        &options.branch,
+        options.create_branch,
+        options.base.as_deref(),
+    )?;
+    git_operations::list_worktrees(repo).map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn remove_worktree(state: State<'_, App>, path: String, force: bool) -> AppResult<()> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::remove_worktree(repo, &path, force).map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn prune_worktrees(state: State<'_, App>) -> AppResult<()> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::prune_worktrees(repo).map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn open_worktree(
+    state: State<'_, App>,
+    app_handle: tauri::AppHandle,
+    path: String,
+) -> AppResult<RepositoryInfo> {
+    let worktree_path = {
+        let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+        let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+        git_operations::list_worktrees(repo)?
+            .into_iter()
+            .find(|w| w.path == path.trim_end_matches(['/', '\\']))
+            .ok_or(AppError::Git(format!("'{}' is not a worktree of this repository", path)))?
+            .path
+    };
+    open_repository(state, app_handle, worktree_path)
+}
+
+#[tauri::command]
+fn list_lfs_files(state: State<'_, App>) -> AppResult<Vec<LfsFileInfo>> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::list_lfs_files(repo).map_err(AppError::Git)
+}
+
+async fn run_lfs_operation(
+    state: State<'_, App>,
+    app_handle: tauri::AppHandle,
+    operation: &'static str,
+    paths: Vec<String>,
+) -> AppResult<()> {
+    let (path, ssh_key) = {
+        let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+        let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+        let path = repo.workdir().ok_or(AppError::Git("No workdir".to_string()))?.to_path_buf();
+        (path, state.settings.ssh_key_path.clone())
+    };
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let repo = git_operations::open_repository(path.to_str().ok_or("Invalid path")?)?;
+        git_operations::run_lfs_command(&repo, operation, &paths, ssh_key.as_deref(), |progress| {
+            let _ = app_handle.emit("lfs-progress", progress);
+        })
+        .map_err(AppError::Git)
+    })
+    .await
+    .map_err(|e| AppError::Git(format!("Spawn error: {}", e)))?
+}
+
+#[tauri::command]
+async fn lfs_fetch(state: State<'_, App>, app_handle: tauri::AppHandle, paths: Vec<String>) -> AppResult<()> {
+    run_lfs_operation(state, app_handle, "fetch", paths).await
+}
+
+#[tauri::command]
+async fn lfs_pull(state: State<'_, App>, app_handle: tauri::AppHandle, paths: Vec<String>) -> AppResult<()> {
+    run_lfs_operation(state, app_handle, "pull", paths).await
+}
+
+#[tauri::command]
+fn bisect_status(state: State<'_, App>) -> AppResult<BisectStatus> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::bisect_status(repo).map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn bisect_start(state: State<'_, App>, bad: String, good: Vec<String>) -> AppResult<BisectStatus> {
     let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
     let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
-    git_operations::discard_all_changes(repo).map_err(AppError::Git)
+    git_operations::bisect_start(repo, &bad, &good).map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn bisect_mark(
+    state: State<'_, App>,
+    verdict: String,
+    sha: Option<String>,
+) -> AppResult<BisectStatus> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::bisect_mark(repo, &verdict, sha.as_deref()).map_err(AppError::Git)
+}
+
+#[tauri::command]
+fn bisect_reset(state: State<'_, App>) -> AppResult<()> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+    git_operations::bisect_reset(repo).map_err(AppError::Git)
+}
+
+/// Runs a test command at each bisect step in the background, emitting a "bisect-step" event
+/// per tested commit.
+#[tauri::command]
+async fn bisect_run(
+    state: State<'_, App>,
+    app_handle: tauri::AppHandle,
+    command: String,
+) -> AppResult<BisectStatus> {
+    let path = {
+        let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+        let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+        repo.path().to_path_buf()
+    };
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let repo = git_operations::open_repository(path.to_str().ok_or("Invalid path")?)?;
+        git_operations::bisect_run(&repo, &command, |step| {
+            let _ = app_handle.emit("bisect-step", step.clone());
+        })
+        .map_err(AppError::Git)
+    })
+    .await
+    .map_err(|e| AppError::Git(format!("Spawn error: {}", e)))?
+}
+
+/// Searches history in the background, emitting "commit-search-page" events as matches come in.
+/// Starting another search or calling `cancel_commit_search` stops this one. `search_id` must be
+/// non-zero, since 0 marks that no search is running.
+#[tauri::command]
+async fn search_commits(
+    state: State<'_, App>,
+    app_handle: tauri::AppHandle,
+    search_id: u64,
+    options: CommitSearchOptions,
+) -> AppResult<CommitSearchSummary> {
+    if search_id == 0 {
+        return Err(AppError::Git("Search id must be non-zero".to_string()));
+    }
+    let (path, active_search) = {
+        let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+        let repo = state.repo.as_ref().ok_or(AppError::Git("No repository open".to_string()))?;
+        (repo.path().to_path_buf(), state.active_search.clone())
+    };
+    active_search.store(search_id, Ordering::SeqCst);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let repo = git_operations::open_repository(path.to_str().ok_or("Invalid path")?)?;
+        git_operations::search_commits(
+            &repo,
+            &options,
+            || active_search.load(Ordering::SeqCst) != search_id,
+            |matches, scanned| {
+                let page = CommitSearchPage { search_id, matches, scanned };
+                let _ = app_handle.emit("commit-search-page", page);
+            },
+        )
+        .map_err(AppError::Git)
+    })
+    .await
+    .map_err(|e| AppError::Git(format!("Spawn error: {}", e)))?
+}
+
+#[tauri::command]
+fn cancel_commit_search(state: State<'_, App>) -> AppResult<()> {
+    let state = state.0.lock().map_err(|_| AppError::Lock("Failed to acquire lock".to_string()))?;
+    state.active_search.store(0, Ordering::SeqCst);
+    Ok(())
 }
 
 #[tauri::command]
@@ -491,6 +1028,8 @@ async fn get_repositories_info(
             is_dirty: false,
             ahead: 0,
             behind: 0,
+            is_worktree: false,
+            main_repository_path: None,
         });
     }
 
@@ -525,13 +1064,16 @@ pub fn run() {
             if let Some(path) = &settings.last_opened_repository {
                 if let Ok(opened_repo) = git_operations::open_repository(path) {
                     repo = Some(opened_repo);
-                    watcher = start_watcher(app_handle.clone(), path);
+                    watcher = watcher::start_watcher(app_handle.clone(), path);
                 }
             }
+            let status_cache = status_cache_for(&watcher);
             app.manage(App(Mutex::new(AppState {
                 repo,
                 settings,
                 watcher,
+                status_cache,
+                active_search: Arc::new(AtomicU64::new(0)),
             })));
             Ok(())
         })
@@ -539,22 +1081,49 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .invoke_handler(tauri::generate_handler![
             open_repository,
+            init_repository,
+            list_init_templates,
             clone_repository,
             get_repository_status,
             create_commit,
             amend_commit,
+            is_head_pushed,
             cherry_pick,
             revert_commit,
+            get_commit_identity,
+            get_commit_template,
+            lint_commit_message,
+            get_coauthor_candidates,
             discard_all_changes,
             stage_files,
             unstage_files,
             discard_changes,
+            discard_paths,
+            discard_hunks,
+            restore_discarded,
             get_branches,
             create_branch,
             checkout_branch,
             get_commit_diff,
+            get_combined_diff,
             get_commit_history,
             get_diff,
+            get_staged_diff,
+            get_unstaged_diff,
+            get_diff_files,
+            get_file_diff,
+            get_image_diff,
+            export_commit_patches,
+            export_diff_patch,
+            apply_patch,
+            search_commits,
+            cancel_commit_search,
+            bisect_status,
+            bisect_start,
+            bisect_mark,
+            bisect_reset,
+            bisect_run,
+            compare_revisions,
             push_changes,
             pull_changes,
             fetch_changes,
@@ -563,6 +1132,19 @@ pub fn run() {
             list_stashes,
             get_conflicts,
             resolve_conflict,
+            list_submodules,
+            init_submodules,
+            update_submodules,
+            sync_submodules,
+            open_submodule,
+            list_worktrees,
+            add_worktree,
+            remove_worktree,
+            prune_worktrees,
+            open_worktree,
+            list_lfs_files,
+            lfs_fetch,
+            lfs_pull,
             get_settings,
             save_settings,
             set_remote_url,
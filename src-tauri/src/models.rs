@@ -7,13 +7,57 @@ pub struct RepositoryInfo {
     pub is_dirty: bool,
     pub ahead: usize,
     pub behind: usize,
+    pub is_worktree: bool, // a linked worktree rather than the main checkout
+    pub main_repository_path: Option<String>, // set for linked worktrees
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WorktreeInfo {
+    pub name: Option<String>, // None for the main worktree
+    pub path: String,
+    pub branch: Option<String>, // None when detached or missing
+    pub head: Option<String>,
+    pub is_main: bool,
+    pub is_current: bool,
+    pub is_locked: bool,
+    pub lock_reason: Option<String>,
+    pub is_prunable: bool, // its directory is gone
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorktreeOptions {
+    pub path: String,
+    pub branch: String,
+    #[serde(default)]
+    pub create_branch: bool, // create `branch` starting at `base` (or HEAD)
+    pub base: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FileStatus {
     pub path: String,
-    pub status: String, // "modified", "added", "deleted", "untracked"
-    pub staged: bool,
+    pub status: String, // summary: "modified", "added", "deleted", "untracked", "renamed", "conflicted", ...
+    pub staged: bool, // has staged changes; the file may also have unstaged ones
+    pub is_submodule: bool,
+    pub index_status: Option<String>, // HEAD -> index: "added", "modified", "deleted", "renamed", "typechange"
+    pub worktree_status: Option<String>, // index -> workdir: "untracked", "modified", "deleted", "renamed", "typechange"
+    pub old_path: Option<String>, // set for renames
+    pub conflicted: bool,
+    pub ignored: bool,
+    pub submodule_state: Option<String>, // "clean", "new-commits", "modified-content", "untracked-content", "uninitialized"
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SubmoduleInfo {
+    pub name: String,
+    pub path: String,
+    pub url: Option<String>,
+    pub branch: Option<String>,
+    pub recorded_commit: Option<String>, // commit the superproject's index points at
+    pub checked_out_commit: Option<String>, // HEAD of the submodule's working tree
+    pub initialized: bool,
+    pub dirty: bool, // uncommitted or untracked changes inside the submodule
+    pub out_of_sync: bool, // checked-out commit differs from the recorded one
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -27,6 +71,80 @@ pub struct CommitInfo {
     pub parents: Vec<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct CommitSearchOptions {
+    pub query: String,
+    pub messages: bool,
+    pub authors: bool, // author name and email
+    pub committers: bool, // committer name and email
+    pub sha: bool, // SHA prefix
+    pub diff: String, // "none", "string" (like -S) or "regex" (like -G, on changed lines)
+    pub case_sensitive: bool,
+    pub all_refs: bool, // search every branch and tag instead of HEAD
+    pub page_size: usize, // matches per "commit-search-page" event
+    pub limit: usize, // stop after this many matches (0 = no limit)
+}
+
+impl Default for CommitSearchOptions {
+    fn default() -> Self {
+        CommitSearchOptions {
+            query: String::new(),
+            messages: true,
+            authors: true,
+            committers: true,
+            sha: true,
+            diff: "none".to_string(),
+            case_sensitive: false,
+            all_refs: false,
+            page_size: 50,
+            limit: 0,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommitSearchMatch {
+    pub commit: CommitInfo,
+    pub matched: Vec<String>, // "message", "author", "committer", "sha", "diff"
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommitSearchPage {
+    pub search_id: u64,
+    pub matches: Vec<CommitSearchMatch>,
+    pub scanned: usize, // commits looked at so far
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommitSearchSummary {
+    pub total: usize,
+    pub scanned: usize,
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BisectStatus {
+    pub active: bool,
+    pub original_branch: Option<String>, // what bisect_reset returns to
+    pub current: Option<String>, // commit checked out for testing
+    pub bad: Option<String>,
+    pub good: Vec<String>,
+    pub skipped: Vec<String>,
+    pub remaining: usize, // untested candidates, the checked-out one included
+    pub estimated_steps: usize,
+    pub first_bad: Option<CommitInfo>, // set once bisection is finished
+    pub candidates: Vec<String>, // only skipped commits left: any of these may be the first bad one
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BisectStep {
+    pub sha: String,
+    pub exit_code: Option<i32>, // None when the command was killed by a signal
+    pub verdict: String, // "good", "bad" or "skip"
+    pub output: String, // tail of the command's stdout and stderr
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BranchInfo {
     pub name: String,
@@ -40,6 +158,239 @@ pub struct DiffInfo {
     pub additions: usize,
     pub deletions: usize,
     pub diff_text: String,
+    pub lfs: Option<LfsDiffInfo>, // set instead of diff_text for LFS-tracked files
+    pub highlights: Vec<LineHighlight>, // intra-line changes of modified line pairs
+    pub too_large: bool, // over the size/line limits; diff_text is left empty
+    pub binary: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiffFileSummary {
+    pub path: String,
+    pub old_path: Option<String>, // set for renames and copies
+    pub status: String, // "added", "deleted", "modified", "renamed", "copied", "typechange", "untracked"
+    pub additions: Option<usize>, // None in get_diff_files' list, which builds no patches
+    pub deletions: Option<usize>,
+    pub binary: bool,
+    pub too_large: bool, // over max_file_size, or max_diff_lines once counted; hunks aren't loaded
+    pub image: bool, // png, jpg, gif, webp or svg; see get_image_diff
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiffSource {
+    pub kind: String, // "workdir", "staged", "unstaged" or "commit"
+    #[serde(default)]
+    pub sha: Option<String>, // commit to diff when kind is "commit"
+    #[serde(default)]
+    pub parent: usize, // parent index for merge commits
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiffStats {
+    pub files_changed: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiffFileList {
+    pub stats: DiffStats,
+    pub files: Vec<DiffFileSummary>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiffLineInfo {
+    pub origin: String, // " ", "+", "-", or ">"/"<"/"=" for end-of-file newline markers
+    pub content: String, // without the trailing newline
+    pub old_lineno: Option<u32>,
+    pub new_lineno: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiffHunkInfo {
+    pub header: String,
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    pub lines: Vec<DiffLineInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileDiff {
+    pub file: DiffFileSummary,
+    pub hunks: Vec<DiffHunkInfo>, // empty for binary, LFS and too-large files
+    pub highlights: Vec<LineHighlight>,
+    pub lfs: Option<LfsDiffInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImageSide {
+    pub oid: Option<String>, // None for working-tree content
+    pub size: u64, // bytes
+    pub width: Option<u32>, // None when the header can't be read or the content isn't loaded
+    pub height: Option<u32>,
+    pub data: Option<String>, // base64; None above the inline limit or for missing LFS objects
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImageDiff {
+    pub path: String,
+    pub status: String,
+    pub mime_type: String,
+    pub old: Option<ImageSide>, // None when the image was added
+    pub new: Option<ImageSide>, // None when the image was deleted
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApplyPatchOptions {
+    pub path: String, // .patch file or format-patch mbox
+    pub target: String, // "worktree", "index" or "am"
+    #[serde(default)]
+    pub check_only: bool, // dry run: only report what would fail
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PatchFailure {
+    pub path: String,
+    pub line: Option<u32>, // old-side start line of the failing hunk
+    pub hunk: Option<String>, // "@@ ... @@" header of the failing hunk
+    pub expected: Option<String>, // context git searched for and didn't find
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PatchApplyResult {
+    pub applied: bool,
+    pub files: Vec<String>, // paths the patch touches
+    pub failures: Vec<PatchFailure>, // non-empty means nothing was applied
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RevisionComparison {
+    pub from_sha: String,
+    pub to_sha: String,
+    pub merge_base: Option<String>, // set when the diff starts at the merge base ("A...B")
+    pub files: Vec<DiffFileSummary>,
+    pub diffs: Vec<DiffInfo>,
+    pub only_in_from: Vec<CommitInfo>, // newest first, capped; see the counts for totals
+    pub only_in_to: Vec<CommitInfo>,
+    pub only_in_from_count: usize,
+    pub only_in_to_count: usize,
+}
+
+/// How diffs are computed and presented; every field is optional on the wire.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct DiffViewOptions {
+    pub ignore_whitespace: String, // "none", "all", "change" (amount), "eol"
+    pub ignore_blank_lines: bool,
+    pub context_lines: u32,
+    pub interhunk_lines: u32, // merge hunks separated by at most this many unchanged lines
+    pub detect_renames: bool,
+    pub detect_copies: bool,
+    pub rename_threshold: u16, // similarity percentage
+    pub copy_threshold: u16,
+    pub algorithm: String, // "myers", "minimal" or "patience"; histogram isn't supported
+    pub show_binary: bool, // false keeps binary files listed but leaves their diff_text empty
+    pub intraline: String, // "word", "char" or "none"
+    pub max_file_size: u64, // bytes; larger files only get metadata (0 = no limit)
+    pub max_diff_lines: usize, // changed lines above which a file only gets metadata (0 = no limit)
+}
+
+impl Default for DiffViewOptions {
+    fn default() -> Self {
+        DiffViewOptions {
+            ignore_whitespace: "none".to_string(),
+            ignore_blank_lines: false,
+            context_lines: 3,
+            interhunk_lines: 0,
+            detect_renames: true,
+            detect_copies: false,
+            rename_threshold: 50,
+            copy_threshold: 50,
+            algorithm: "myers".to_string(),
+            show_binary: true,
+            intraline: "word".to_string(),
+            max_file_size: 1024 * 1024,
+            max_diff_lines: 20_000,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LineHighlight {
+    pub old_lineno: Option<u32>, // set for a removed ("-") line
+    pub new_lineno: Option<u32>, // set for an added ("+") line
+    pub ranges: Vec<TextRange>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TextRange {
+    pub start: usize, // UTF-16 offsets into the line content (without the +/- prefix), as JS strings index
+    pub end: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LfsObject {
+    pub oid: Option<String>, // sha256; None for working-tree content that isn't a pointer yet
+    pub size: u64,
+    pub present: bool, // object exists in the local LFS store (or is the working-tree file)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LfsDiffInfo {
+    pub old: Option<LfsObject>,
+    pub new: Option<LfsObject>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LfsFileInfo {
+    pub path: String,
+    pub oid: String,
+    pub size: u64,
+    pub present: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LfsProgress {
+    pub operation: String, // "fetch", "pull"
+    pub message: String,
+    pub percent: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RepoEvent {
+    pub repo_path: String, // payload of "index-changed" and "stash-changed"
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HeadChangedEvent {
+    pub repo_path: String,
+    pub old_branch: Option<String>, // None when detached
+    pub new_branch: Option<String>,
+    pub old_oid: Option<String>, // None on an unborn branch
+    pub new_oid: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RefsChangedEvent {
+    pub repo_path: String,
+    pub refs: Vec<String>, // full names of created, moved or deleted refs
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WorktreeChangedEvent {
+    pub repo_path: String,
+    pub paths: Vec<String>, // relative to the working tree; ignored files are left out
+    pub rescan: bool, // events were lost; paths may be incomplete
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RepoStateChangedEvent {
+    pub repo_path: String,
+    pub old_state: String, // "clean", "merge", "rebase", "cherry-pick", "revert", "bisect", "apply-mailbox"
+    pub new_state: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -65,18 +416,158 @@ pub struct Settings {
     pub theme: String,
     pub recent_repositories: Vec<String>,
     pub last_opened_repository: Option<String>,
+    #[serde(default)]
+    pub identity_profiles: Vec<IdentityProfile>,
+    #[serde(default)]
+    pub allow_placeholder_identity: bool,
+}
+
+/// A named commit identity applied to repositories matching its path prefix or remote host.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IdentityProfile {
+    pub name: String, // e.g. "work", "personal"
+    pub user_name: String,
+    pub user_email: String,
+    pub path_prefix: Option<String>,
+    pub remote_host: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommitIdentity {
+    pub name: String,
+    pub email: String,
+    pub source: String, // "repository", "profile:<name>", "git-config", "settings", "placeholder"
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CloneOptions {
     pub url: String,
     pub path: String,
+    pub branch: Option<String>,
+    pub depth: Option<u32>, // shallow clone
+    #[serde(default)]
+    pub single_branch: bool,
+    pub filter: Option<String>, // partial clone, e.g. "blob:none"
+    #[serde(default)]
+    pub bare: bool,
+    #[serde(default)]
+    pub mirror: bool,
+    #[serde(default)]
+    pub recurse_submodules: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InitOptions {
+    pub path: String,
+    pub initial_branch: Option<String>, // defaults to "main"
+    #[serde(default)]
+    pub readme: bool,
+    pub gitignore_template: Option<String>, // name from `list_init_templates`
+    pub license_template: Option<String>,
+    #[serde(default)]
+    pub initial_commit: bool,
+    pub remote_url: Option<String>, // added as "origin"
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InitTemplates {
+    pub gitignore: Vec<String>,
+    pub license: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CommitOptions {
     pub message: String,
     pub files: Vec<String>, // paths to stage
+    #[serde(default)]
+    pub skip_hooks: bool, // like `git commit --no-verify`: skips pre-commit and commit-msg only
+    #[serde(default)]
+    pub trailers: Vec<CommitTrailer>,
+    #[serde(default)]
+    pub sign_off: bool, // append Signed-off-by for the committing identity
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AmendOptions {
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub keep_message: bool, // reuse the message of the commit being amended
+    #[serde(default)]
+    pub reset_author: bool, // take over authorship with the current identity and time
+    #[serde(default)]
+    pub files: Vec<String>, // paths to stage before amending
+    #[serde(default)]
+    pub allow_pushed: bool, // amending a commit the upstream already has requires opt-in
+    #[serde(default)]
+    pub skip_hooks: bool,
+    #[serde(default)]
+    pub trailers: Vec<CommitTrailer>,
+    #[serde(default)]
+    pub sign_off: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommitTrailer {
+    pub key: String, // e.g. "Co-authored-by", "Signed-off-by", "Refs", "Fixes"
+    pub value: String,
+}
+
+/// Per-repository commit message rules, read from `.cyberpunk-commitlint.json`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct CommitLintConfig {
+    pub subject_max_length: usize,
+    pub require_blank_second_line: bool,
+    pub body_wrap_width: Option<usize>,
+    pub conventional: bool, // enforce `type(scope)!: description` subjects
+    pub types: Vec<String>,
+    pub scopes: Vec<String>, // empty = any scope
+    pub require_scope: bool,
+    pub enforce: bool, // block commits with errors instead of only reporting them
+}
+
+impl Default for CommitLintConfig {
+    fn default() -> Self {
+        CommitLintConfig {
+            subject_max_length: 72,
+            require_blank_second_line: true,
+            body_wrap_width: Some(72),
+            conventional: false,
+            types: [
+                "feat", "fix", "docs", "style", "refactor", "perf", "test", "build", "ci", "chore",
+                "revert",
+            ]
+            .iter()
+            .map(|t| t.to_string())
+            .collect(),
+            scopes: Vec::new(),
+            require_scope: false,
+            enforce: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommitLintViolation {
+    pub rule: String,
+    pub message: String,
+    pub line: usize, // 1-based line in the message
+    pub severity: String, // "error", "warning"
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommitLintResult {
+    pub violations: Vec<CommitLintViolation>,
+    pub enforced: bool,
+    pub blocking: bool, // enforced and at least one error
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CoAuthor {
+    pub name: String,
+    pub email: String,
+    pub commit_count: usize,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -94,3 +585,33 @@ pub struct StageResult {
     pub staged: Vec<String>,
     pub warnings: Vec<String>,
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LineSelection {
+    pub old_lineno: Option<u32>, // selects a removed ("-") line
+    pub new_lineno: Option<u32>, // selects an added ("+") line
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiscardHunksOptions {
+    pub path: String,
+    #[serde(default)]
+    pub hunks: Vec<usize>, // indices into the file's get_unstaged_diff (index -> workdir) result
+    #[serde(default)]
+    pub lines: Vec<LineSelection>,
+    #[serde(default)]
+    pub diff_options: DiffViewOptions, // the options the hunks were shown with
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiscardFileResult {
+    pub path: String,
+    pub discarded: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiscardResult {
+    pub snapshot_ref: Option<String>, // refs/safety/discard/...; None when there was nothing to save
+    pub files: Vec<DiscardFileResult>,
+}
@@ -0,0 +1,383 @@
+use crate::git_operations;
+use crate::models::{
+    HeadChangedEvent, RefsChangedEvent, RepoEvent, RepoStateChangedEvent, WorktreeChangedEvent,
+};
+use crate::App;
+use git2::Repository;
+use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager};
+
+/// A burst of events ends once nothing arrives for this long.
+const DEBOUNCE: Duration = Duration::from_millis(150);
+/// A continuous burst (e.g. a large checkout) still refreshes at least this often.
+const MAX_DELAY: Duration = Duration::from_secs(1);
+
+/// Entries of the git directory that affect what the UI shows. Everything else
+/// (objects, logs, FETCH_HEAD, lock files) is noise.
+const GIT_STATE_FILES: &[&str] = &[
+    "HEAD",
+    "index",
+    "packed-refs",
+    "MERGE_HEAD",
+    "CHERRY_PICK_HEAD",
+    "REVERT_HEAD",
+    "logs/refs/stash", // dropping an older stash only rewrites the reflog
+];
+const GIT_STATE_DIRS: &[&str] = &["refs", "rebase-merge", "rebase-apply"];
+
+/// Keeps the notify watcher alive; dropping it stops the event thread.
+pub struct RepoWatcher {
+    _watcher: Arc<Mutex<RecommendedWatcher>>,
+    pub watches_worktree: bool, // working-tree edits are reported, so status can refresh incrementally
+}
+
+/// Canonical locations events are matched against. For linked worktrees `.git` is a file,
+/// so the git directory and the shared common directory are resolved through libgit2.
+struct WatchPaths {
+    git_dir: PathBuf,
+    common_dir: PathBuf,
+    workdir: Option<PathBuf>,
+}
+
+/// What HEAD, the refs and the in-progress operation looked like after the previous batch,
+/// so events can say what changed rather than just that something did.
+struct Snapshot {
+    branch: Option<String>,
+    head: Option<String>,
+    state: &'static str,
+    refs: BTreeMap<String, String>,
+}
+
+impl Snapshot {
+    fn capture(repo: &Repository) -> Self {
+        let head = repo.head().ok();
+        let branch = head
+            .as_ref()
+            .filter(|h| h.is_branch())
+            .and_then(|h| h.shorthand().map(String::from))
+            // An unborn branch has no HEAD reference yet, only the symbolic name
+            .or_else(|| {
+                repo.find_reference("HEAD")
+                    .ok()
+                    .and_then(|r| r.symbolic_target().map(String::from))
+                    .and_then(|t| t.strip_prefix("refs/heads/").map(String::from))
+            });
+        let mut refs = BTreeMap::new();
+        if let Ok(references) = repo.references() {
+            for reference in references.flatten() {
+                if let (Some(name), Some(target)) = (reference.name(), reference.target()) {
+                    if name != "refs/stash" {
+                        refs.insert(name.to_string(), target.to_string());
+                    }
+                }
+            }
+        }
+        Snapshot {
+            branch,
+            head: head.and_then(|h| h.target()).map(|oid| oid.to_string()),
+            state: git_operations::repository_state_name(repo.state()),
+            refs,
+        }
+    }
+
+    /// Names of refs that were created, moved or deleted since `self`.
+    fn changed_refs(&self, current: &Snapshot) -> Vec<String> {
+        let names: BTreeSet<&String> = self.refs.keys().chain(current.refs.keys()).collect();
+        names
+            .into_iter()
+            .filter(|name| self.refs.get(*name) != current.refs.get(*name))
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct Batch {
+    worktree_paths: BTreeSet<String>,
+    git_paths: BTreeSet<String>,
+    rescan: bool,
+}
+
+pub fn start_watcher(app_handle: tauri::AppHandle, repo_path: &str) -> Option<RepoWatcher> {
+    let repo = git_operations::open_repository(repo_path).ok()?;
+    let git_dir = repo.path().canonicalize().ok()?;
+    let common_dir = git_operations::common_git_dir(&repo)
+        .canonicalize()
+        .unwrap_or_else(|_| git_dir.clone());
+    let workdir = repo.workdir().and_then(|w| w.canonicalize().ok());
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher = RecommendedWatcher::new(tx, Config::default()).ok()?;
+
+    // HEAD, index, MERGE_HEAD and the rebase directories live in the (per-worktree) git dir;
+    // refs and packed-refs are shared through the common dir
+    watcher.watch(&git_dir, RecursiveMode::NonRecursive).ok()?;
+    if common_dir != git_dir {
+        let _ = watcher.watch(&common_dir, RecursiveMode::NonRecursive);
+    }
+    let _ = watcher.watch(&common_dir.join("refs"), RecursiveMode::Recursive);
+    let _ = watcher.watch(&common_dir.join("logs").join("refs"), RecursiveMode::NonRecursive);
+
+    let watches_worktree = match &workdir {
+        Some(workdir) => watch_worktree(&mut watcher, &repo, workdir),
+        None => false,
+    };
+
+    let watcher = Arc::new(Mutex::new(watcher));
+    let paths = WatchPaths {
+        git_dir,
+        common_dir,
+        workdir,
+    };
+    let weak = Arc::downgrade(&watcher);
+    let repo_path = repo_path.to_string();
+    std::thread::spawn(move || run(rx, repo, paths, weak, app_handle, repo_path));
+
+    Some(RepoWatcher {
+        _watcher: watcher,
+        watches_worktree,
+    })
+}
+
+/// Watches each non-ignored top-level directory recursively instead of the whole tree,
+/// so `.git`, `node_modules`, `target` and the like don't use up inotify watches.
+fn watch_worktree(watcher: &mut RecommendedWatcher, repo: &Repository, workdir: &Path) -> bool {
+    if watcher.watch(workdir, RecursiveMode::NonRecursive).is_err() {
+        return false;
+    }
+    let entries = match std::fs::read_dir(workdir) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+    for entry in entries.flatten() {
+        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !is_dir || name == ".git" || is_ignored(repo, &name, true) {
+            continue;
+        }
+        // Missing a directory would leave status stale, so give up on incremental status
+        if watcher.watch(&entry.path(), RecursiveMode::Recursive).is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+fn is_ignored(repo: &Repository, relative: &str, is_dir: bool) -> bool {
+    let path = if is_dir {
+        format!("{}/", relative)
+    } else {
+        relative.to_string()
+    };
+    repo.is_path_ignored(path).unwrap_or(false)
+}
+
+fn run(
+    rx: Receiver<notify::Result<Event>>,
+    repo: Repository,
+    paths: WatchPaths,
+    watcher: Weak<Mutex<RecommendedWatcher>>,
+    app_handle: tauri::AppHandle,
+    repo_path: String,
+) {
+    let mut snapshot = Snapshot::capture(&repo);
+
+    // The sender lives inside the watcher, so recv fails once the watcher is dropped
+    while let Ok(first) = rx.recv() {
+        let mut events = vec![first];
+        let started = Instant::now();
+        loop {
+            let wait = DEBOUNCE.min(MAX_DELAY.saturating_sub(started.elapsed()));
+            match rx.recv_timeout(wait) {
+                Ok(event) => events.push(event),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        let mut batch = Batch::default();
+        for result in events {
+            match result {
+                Ok(event) => collect_event(&event, &repo, &paths, &watcher, &mut batch),
+                Err(e) => {
+                    eprintln!("watcher error: {:?}", e);
+                    batch.rescan = true;
+                }
+            }
+        }
+        if batch.worktree_paths.is_empty() && batch.git_paths.is_empty() && !batch.rescan {
+            continue;
+        }
+
+        update_status_cache(&app_handle, &paths, &batch);
+        snapshot = emit_events(&app_handle, &repo, &repo_path, snapshot, batch);
+    }
+}
+
+/// Turns a batch into typed events, each sent only when its part of the repository changed.
+fn emit_events(
+    app_handle: &tauri::AppHandle,
+    repo: &Repository,
+    repo_path: &str,
+    previous: Snapshot,
+    batch: Batch,
+) -> Snapshot {
+    let event = || RepoEvent {
+        repo_path: repo_path.to_string(),
+    };
+    let touched = |name: &str| batch.rescan || batch.git_paths.contains(name);
+
+    if !batch.worktree_paths.is_empty() || batch.rescan {
+        let _ = app_handle.emit(
+            "worktree-changed",
+            WorktreeChangedEvent {
+                repo_path: repo_path.to_string(),
+                paths: batch.worktree_paths.iter().cloned().collect(),
+                rescan: batch.rescan,
+            },
+        );
+    }
+    if touched("index") {
+        let _ = app_handle.emit("index-changed", event());
+    }
+    if touched("refs/stash") || touched("logs/refs/stash") {
+        let _ = app_handle.emit("stash-changed", event());
+    }
+    if batch.git_paths.is_empty() && !batch.rescan {
+        return previous;
+    }
+
+    let current = Snapshot::capture(repo);
+    if current.branch != previous.branch || current.head != previous.head {
+        let _ = app_handle.emit(
+            "head-changed",
+            HeadChangedEvent {
+                repo_path: repo_path.to_string(),
+                old_branch: previous.branch.clone(),
+                new_branch: current.branch.clone(),
+                old_oid: previous.head.clone(),
+                new_oid: current.head.clone(),
+            },
+        );
+    }
+    let refs = previous.changed_refs(&current);
+    if !refs.is_empty() {
+        let _ = app_handle.emit(
+            "refs-changed",
+            RefsChangedEvent {
+                repo_path: repo_path.to_string(),
+                refs,
+            },
+        );
+    }
+    if current.state != previous.state {
+        let _ = app_handle.emit(
+            "repo-state-changed",
+            RepoStateChangedEvent {
+                repo_path: repo_path.to_string(),
+                old_state: previous.state.to_string(),
+                new_state: current.state.to_string(),
+            },
+        );
+    }
+    current
+}
+
+fn collect_event(
+    event: &Event,
+    repo: &Repository,
+    paths: &WatchPaths,
+    watcher: &Weak<Mutex<RecommendedWatcher>>,
+    batch: &mut Batch,
+) {
+    if event.need_rescan() {
+        batch.rescan = true;
+    }
+    for path in &event.paths {
+        if let Some(relative) = git_state_path(path, paths) {
+            batch.git_paths.insert(relative);
+            continue;
+        }
+        let workdir = match &paths.workdir {
+            Some(w) => w,
+            None => continue,
+        };
+        let relative = match path.strip_prefix(workdir) {
+            Ok(r) if !r.as_os_str().is_empty() => git_operations::normalize_path(r),
+            _ => continue,
+        };
+        if relative == ".git" || relative.starts_with(".git/") {
+            continue;
+        }
+        let is_dir = path.is_dir();
+        if is_ignored(repo, &relative, is_dir) {
+            continue;
+        }
+
+        // New top-level directories aren't covered by the existing watches yet
+        if is_dir && matches!(event.kind, EventKind::Create(_)) && path.parent() == Some(workdir.as_path()) {
+            if let Some(watcher) = watcher.upgrade() {
+                if let Ok(mut watcher) = watcher.lock() {
+                    let _ = watcher.watch(path, RecursiveMode::Recursive);
+                }
+            }
+        }
+        batch.worktree_paths.insert(relative);
+    }
+}
+
+/// Maps a path inside the git directory to the part the UI cares about, e.g. "HEAD" or
+/// "refs/heads/main". Returns None for paths outside it and for irrelevant entries.
+fn git_state_path(path: &Path, paths: &WatchPaths) -> Option<String> {
+    let relative = path
+        .strip_prefix(&paths.git_dir)
+        .or_else(|_| path.strip_prefix(&paths.common_dir))
+        .ok()?;
+    let relative = git_operations::normalize_path(relative);
+    if relative.ends_with(".lock") {
+        return None;
+    }
+    let first = relative.split('/').next().unwrap_or_default();
+    let is_state_file = GIT_STATE_FILES.contains(&relative.as_str());
+    if is_state_file || GIT_STATE_DIRS.contains(&first) {
+        Some(relative)
+    } else {
+        None
+    }
+}
+
+/// Feeds changed paths into the status cache, as long as the app still has this repository open.
+fn update_status_cache(app_handle: &tauri::AppHandle, paths: &WatchPaths, batch: &Batch) {
+    let app = match app_handle.try_state::<App>() {
+        Some(app) => app,
+        None => return,
+    };
+    let mut state = match app.0.lock() {
+        Ok(state) => state,
+        Err(_) => return,
+    };
+    let same_repo = state
+        .repo
+        .as_ref()
+        .and_then(|r| r.path().canonicalize().ok())
+        .is_some_and(|p| p == paths.git_dir);
+    if !same_repo {
+        return;
+    }
+
+    // New ignore rules can change the status of any number of files
+    let ignore_changed = batch
+        .worktree_paths
+        .iter()
+        .any(|p| p == ".gitignore" || p.ends_with("/.gitignore"));
+    if batch.rescan || ignore_changed {
+        state.status_cache.invalidate();
+    } else {
+        state.status_cache.mark_paths(batch.worktree_paths.iter().cloned());
+    }
+}
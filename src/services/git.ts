@@ -139,7 +139,7 @@ export const gitService = {
    * 修正最後一次 commit
    */
   async amendCommit(message: string): Promise<string> {
-    return await invoke("amend_commit", { message });
+    return await invoke("amend_commit", { options: { message } });
   },
 
   /**